@@ -1,7 +1,7 @@
-use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use postcard_forth::{deser_fields_ref, ser_fields_ref, DeserStream, SerStream};
-use postcard_forth_derive::{Deserialize, Serialize};
+use postcard_forth::{from_bytes, take_from_bytes, to_slice, Error, MaxSize};
+use postcard_forth_derive::{Deserialize, MaxSize, Serialize};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct Alpha {
@@ -37,6 +37,243 @@ enum Dolsot {
     }
 }
 
+/// Fields named like the locals in the generated code
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shadowed {
+    Named {
+        outcome: u8,
+        var: u8,
+        ptr: u8,
+        stream: u8,
+        base: u8,
+        variant: u8,
+        func: u8,
+    },
+    Empty,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Reading {
+    id: u8,
+    value: i32,
+    scale: Option<f32>,
+    raw: [u16; 4],
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Report {
+    Empty,
+    One(Reading),
+    Two(Reading, Reading),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Borrowed<'a> {
+    name: &'a str,
+    data: &'a [u8],
+    tag: &'a [u8; 2],
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Log<'a, 'b> {
+    Line(&'a str),
+    Pair {
+        left: Borrowed<'a>,
+        right: &'b str,
+    },
+    Empty,
+}
+
+/// Declares its own `'de`, which is used as the input lifetime
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Named<'de> {
+    s: &'de str,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Quoted<'a, 'de> {
+    Both(&'a str, Named<'de>),
+    One(&'de str),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Expr {
+    Lit(u32),
+    Add(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Chain {
+    val: u8,
+    next: Option<Box<Chain>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Marker;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Tagged {
+    tag: char,
+    marker: Marker,
+    unit: (),
+    kind: core::marker::PhantomData<Reading>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Wrapper<T> {
+    inner: T,
+    count: u8,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Msg<'a, T, const N: usize> {
+    Val(T),
+    Text { text: &'a str, tag: [u8; N] },
+    Nothing,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Frame<const N: usize> {
+    Data([u8; N]),
+    Ack,
+}
+
+trait Units {
+    type Raw;
+}
+
+struct Celsius;
+
+impl Units for Celsius {
+    type Raw = i16;
+}
+
+// `Celsius` itself can't be serialized, so the inferred `U: Serialize`
+// bound would never hold
+#[derive(Serialize, Deserialize)]
+#[postcard_forth(bound(
+    serialize = "U::Raw: postcard_forth::Serialize",
+    deserialize = "U::Raw: postcard_forth::Deserialize<'de>"
+))]
+struct Sample<U: Units> {
+    raw: U::Raw,
+    units: core::marker::PhantomData<U>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Session {
+    id: u16,
+    #[postcard_forth(skip)]
+    cache: Option<[u8; 4]>,
+    #[postcard_forth(skip, default = "started")]
+    started: u32,
+    seq: u8,
+}
+
+fn started() -> u32 {
+    1234
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Event {
+    Tick(u8, #[postcard_forth(skip)] u64, u8),
+    Login {
+        #[postcard_forth(skip, default = "started")]
+        at: u32,
+        user: u8,
+    },
+}
+
+/// Neither sent nor `Default`, so it can only be used in skipped fields
+#[derive(Debug, PartialEq)]
+struct Handle(u32);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Cached<H> {
+    key: u8,
+    #[postcard_forth(skip)]
+    handle: Option<H>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+enum Lookup<H> {
+    Miss,
+    Hit(u8, #[postcard_forth(skip, default = "no_handle")] Option<H>),
+}
+
+fn no_handle<H>() -> Option<H> {
+    None
+}
+
+/// Encodes an `Ipv4Addr` as its four octets
+mod ipv4 {
+    use core::ptr::NonNull;
+    use postcard_forth::{DeserStream, Result, SerStream};
+    use std::net::Ipv4Addr;
+
+    pub const MAX_SIZE: usize = 4;
+    pub const MIN_SIZE: usize = 4;
+
+    pub unsafe fn ser(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let addr: &Ipv4Addr = base.cast::<Ipv4Addr>().as_ref();
+        stream.push_n(&addr.octets())
+    }
+
+    pub unsafe fn deser(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let b = stream.pop_n_buffered(4)?;
+        let addr = Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+        base.cast::<Ipv4Addr>().as_ptr().write(addr);
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+struct Peer {
+    #[postcard_forth(with = "ipv4")]
+    addr: std::net::Ipv4Addr,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Route {
+    Via(#[postcard_forth(with = "ipv4")] std::net::Ipv4Addr),
+    Direct,
+}
+
+/// Stands in for a crate that re-exports postcard-forth
+mod sdk {
+    pub use postcard_forth as wire;
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, MaxSize)]
+#[postcard_forth(crate = "sdk::wire")]
+enum Reexported<T> {
+    One(T),
+    Two { a: T, b: u8 },
+}
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Tracked(u8);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Tracker {
+    Pair(Tracked, Tracked, bool),
+    Named {
+        left: Tracked,
+        right: Tracked,
+        flag: bool,
+    }
+}
+
 fn main() {
     println!("Hello, world!");
     let a = Alpha {
@@ -50,12 +287,7 @@ fn main() {
     };
 
     let mut outa = [0u8; 64];
-    let mut sers = SerStream::from(outa.as_mut_slice());
-    unsafe {
-        ser_fields_ref(&mut sers, &a).unwrap();
-    }
-    let remain = sers.remain();
-    let used = outa.len() - remain;
+    let used = to_slice(&a, &mut outa).unwrap().len();
     assert_eq!(used, 17);
     assert_eq!(
         &outa[..used],
@@ -66,14 +298,8 @@ fn main() {
 
     let bytes = &[1, 128, 2, 128, 128, 4, 255, 129, 2, 129, 128, 4, 4, 1, 2, 3, 4];
 
-    let mut desers = DeserStream::from(bytes.as_slice());
-    let mut out = MaybeUninit::<Alpha>::uninit();
-    unsafe {
-        deser_fields_ref(&mut desers, &mut out).unwrap();
-    }
-    let remain = desers.remain();
-    assert_eq!(remain, 0);
-    let out = unsafe { out.assume_init() };
+    let (out, remain) = take_from_bytes::<Alpha>(bytes.as_slice()).unwrap();
+    assert!(remain.is_empty());
     assert_eq!(
         a,
         out,
@@ -92,12 +318,7 @@ fn main() {
     });
 
     let mut outa = [0u8; 64];
-    let mut sers = SerStream::from(outa.as_mut_slice());
-    unsafe {
-        ser_fields_ref(&mut sers, &a).unwrap();
-    }
-    let remain = sers.remain();
-    let used = outa.len() - remain;
+    let used = to_slice(&a, &mut outa).unwrap().len();
     assert_eq!(used, 18);
     assert_eq!(
         &outa[..used],
@@ -108,18 +329,176 @@ fn main() {
 
     let bytes = &[1, 1, 128, 2, 128, 128, 4, 255, 129, 2, 129, 128, 4, 4, 1, 2, 3, 4];
 
-    let mut desers = DeserStream::from(bytes.as_slice());
-    let mut out = MaybeUninit::<Dolsot>::uninit();
-    unsafe {
-        deser_fields_ref(&mut desers, &mut out).unwrap();
-    }
-    let remain = desers.remain();
-    assert_eq!(remain, 0);
-    let out = unsafe { out.assume_init() };
+    let (out, remain) = take_from_bytes::<Dolsot>(bytes.as_slice()).unwrap();
+    assert!(remain.is_empty());
     assert_eq!(
         a,
         out,
     );
 
+    // ---
+
+    let s = Shadowed::Named { outcome: 1, var: 2, ptr: 3, stream: 4, base: 5, variant: 6, func: 7 };
+    let used = to_slice(&s, &mut outa).unwrap();
+    assert_eq!(used, &[0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(from_bytes::<Shadowed>(used).unwrap(), s);
+
+    // ===
+
+    // Sizes are known up front for bounded types
+    assert_eq!(Reading::MAX_SIZE, 1 + 5 + 5 + 4 * 3);
+    assert_eq!(Reading::MIN_SIZE, 1 + 1 + 1 + 4);
+    assert_eq!(Report::MAX_SIZE, 1 + 2 * Reading::MAX_SIZE);
+    assert_eq!(Report::MIN_SIZE, 1);
+
+    let r = Report::Two(
+        Reading { id: 255, value: i32::MIN, scale: Some(1.5), raw: [u16::MAX; 4] },
+        Reading { id: 0, value: i32::MAX, scale: None, raw: [0; 4] },
+    );
+    let mut outr = [0u8; Report::MAX_SIZE];
+    let used = to_slice(&r, &mut outr).unwrap().len();
+    assert_eq!(used, 1 + Reading::MAX_SIZE + (1 + 5 + 1 + 4));
+    assert_eq!(from_bytes::<Report>(&outr[..used]).unwrap(), r);
+
+    // ===
+
+    // Borrowed types point into the input buffer
+    let l = Log::Pair {
+        left: Borrowed { name: "temp", data: &[1, 2, 3], tag: b"ok" },
+        right: "sensor",
+    };
+    let mut outl = [0u8; 64];
+    let used = to_slice(&l, &mut outl).unwrap();
+    assert_eq!(used, &[1, 4, b't', b'e', b'm', b'p', 3, 1, 2, 3, b'o', b'k', 6, b's', b'e', b'n', b's', b'o', b'r']);
+    let out = from_bytes::<Log>(used).unwrap();
+    assert_eq!(out, l);
+    let Log::Pair { left, .. } = out else { unreachable!() };
+    assert!(used.as_ptr_range().contains(&left.name.as_ptr()));
+    assert_eq!(from_bytes::<Log>(&[0, 2, b'h', b'i']).unwrap(), Log::Line("hi"));
+
+    let q = Quoted::Both("a", Named { s: "bc" });
+    let used = to_slice(&q, &mut outl).unwrap();
+    assert_eq!(used, &[0, 1, b'a', 2, b'b', b'c']);
+    assert_eq!(from_bytes::<Quoted>(used).unwrap(), q);
+    assert_eq!(from_bytes::<Named>(&[1, b'x']).unwrap(), Named { s: "x" });
+
+    // ===
+
+    // Recursive types go through Box
+    let e = Expr::Add(Box::new(Expr::Lit(1)), Box::new(Expr::Neg(Box::new(Expr::Lit(2)))));
+    let mut oute = [0u8; 16];
+    let used = to_slice(&e, &mut oute).unwrap();
+    assert_eq!(used, &[1, 0, 1, 2, 0, 2]);
+    assert_eq!(from_bytes::<Expr>(used).unwrap(), e);
+    assert_eq!(from_bytes::<Expr>(&[1, 0, 1, 2, 0]), Err(Error::UnexpectedEnd));
+
+    let c = Chain { val: 1, next: Some(Box::new(Chain { val: 2, next: None })) };
+    let used = to_slice(&c, &mut oute).unwrap();
+    assert_eq!(used, &[1, 1, 2, 0]);
+    assert_eq!(from_bytes::<Chain>(used).unwrap(), c);
+
+    // ===
+
+    // Markers and units take up no space
+    let t = Tagged { tag: 'é', marker: Marker, unit: (), kind: core::marker::PhantomData };
+    let used = to_slice(&t, &mut oute).unwrap();
+    assert_eq!(used, &[2, 0xC3, 0xA9]);
+    assert_eq!(from_bytes::<Tagged>(used).unwrap(), t);
+    assert_eq!(Tagged::MAX_SIZE, 5);
+
+    // ===
+
+    // Generic structs and enums
+    let m: Msg<Wrapper<u16>, 2> = Msg::Val(Wrapper { inner: 300, count: 1 });
+    let used = to_slice(&m, &mut oute).unwrap();
+    assert_eq!(used, &[0, 172, 2, 1]);
+    let m: Msg<Wrapper<u16>, 2> = Msg::Text { text: "hi", tag: [7, 8] };
+    let used = to_slice(&m, &mut oute).unwrap();
+    assert_eq!(used, &[1, 2, b'h', b'i', 7, 8]);
+    assert_eq!(from_bytes::<Msg<Wrapper<u16>, 2>>(used).unwrap(), m);
+    let m: Msg<u8, 0> = Msg::Nothing;
+    assert_eq!(to_slice(&m, &mut oute).unwrap(), &[2]);
+    assert_eq!(from_bytes::<Wrapper<String>>(&[2, b'h', b'i', 3]).unwrap(), Wrapper { inner: String::from("hi"), count: 3 });
+
+    // Bounds on an associated type instead of the parameter
+    let s = Sample::<Celsius> { raw: -2, units: core::marker::PhantomData };
+    let used = to_slice(&s, &mut oute).unwrap();
+    assert_eq!(used, &[3]);
+    assert_eq!(from_bytes::<Sample<Celsius>>(used).unwrap().raw, -2);
+
+    let f = Frame::Data([1, 2, 3]);
+    let used = to_slice(&f, &mut oute).unwrap();
+    assert_eq!(used, &[0, 1, 2, 3]);
+    assert_eq!(from_bytes::<Frame<3>>(used).unwrap(), f);
+    assert_eq!(from_bytes::<Frame<3>>(&[1]).unwrap(), Frame::Ack);
+    assert_eq!(Frame::<3>::MAX_SIZE, 4);
+
+    // ===
+
+    // Skipped fields are left out, and filled in on the way back
+    let s = Session { id: 300, cache: Some([1; 4]), started: 9, seq: 2 };
+    let used = to_slice(&s, &mut oute).unwrap();
+    assert_eq!(used, &[172, 2, 2]);
+    assert_eq!(from_bytes::<Session>(used).unwrap(), Session { id: 300, cache: None, started: 1234, seq: 2 });
+    assert_eq!(Session::MAX_SIZE, 3 + 1);
+
+    let e = Event::Tick(1, 99, 2);
+    let used = to_slice(&e, &mut oute).unwrap();
+    assert_eq!(used, &[0, 1, 2]);
+    assert_eq!(from_bytes::<Event>(used).unwrap(), Event::Tick(1, 0, 2));
+    let e = Event::Login { at: 5, user: 7 };
+    let used = to_slice(&e, &mut oute).unwrap();
+    assert_eq!(used, &[1, 7]);
+    assert_eq!(from_bytes::<Event>(used).unwrap(), Event::Login { at: 1234, user: 7 });
+    assert_eq!(Event::MAX_SIZE, 3);
+
+    // Type parameters only used by skipped fields need no wire traits
+    let c = Cached { key: 4, handle: Some(Handle(1)) };
+    let used = to_slice(&c, &mut oute).unwrap();
+    assert_eq!(used, &[4]);
+    assert_eq!(from_bytes::<Cached<Handle>>(used).unwrap(), Cached { key: 4, handle: None });
+    assert_eq!(Cached::<Handle>::MAX_SIZE, 1);
+    let l = Lookup::Hit(9, Some(Handle(2)));
+    let used = to_slice(&l, &mut oute).unwrap();
+    assert_eq!(used, &[1, 9]);
+    assert_eq!(from_bytes::<Lookup<Handle>>(used).unwrap(), Lookup::Hit(9, None));
+
+    // ===
+
+    // Fields with their own codec
+    let p = Peer { addr: std::net::Ipv4Addr::new(10, 0, 0, 1), port: 300 };
+    let used = to_slice(&p, &mut oute).unwrap();
+    assert_eq!(used, &[10, 0, 0, 1, 172, 2]);
+    assert_eq!(from_bytes::<Peer>(used).unwrap(), p);
+    assert_eq!(Peer::MAX_SIZE, 4 + 3);
+    let r = Route::Via(std::net::Ipv4Addr::LOCALHOST);
+    let used = to_slice(&r, &mut oute).unwrap();
+    assert_eq!(used, &[0, 127, 0, 0, 1]);
+    assert_eq!(from_bytes::<Route>(used).unwrap(), r);
+    assert_eq!(from_bytes::<Route>(&[0, 127, 0]), Err(Error::UnexpectedEnd));
+    assert_eq!(from_bytes::<Route>(&[1]).unwrap(), Route::Direct);
+
+    // ===
+
+    // Generated code can go through a re-export
+    let r = Reexported::Two { a: 300u16, b: 4 };
+    let used = to_slice(&r, &mut oute).unwrap();
+    assert_eq!(used, &[1, 172, 2, 4]);
+    assert_eq!(from_bytes::<Reexported<u16>>(used).unwrap(), r);
+    assert_eq!(from_bytes::<Reexported<u16>>(&[0, 5]).unwrap(), Reexported::One(5));
+    assert_eq!(Reexported::<u16>::MAX_SIZE, 1 + 3 + 1);
+
+    // ===
+
+    // Fields that were decoded before the failure are dropped
+    let res = from_bytes::<Tracker>(&[0, 1, 2, 9]);
+    assert_eq!(res, Err(Error::BadBool));
+    assert_eq!(DROPS.swap(0, Ordering::Relaxed), 2);
+
+    let res = from_bytes::<Tracker>(&[1, 1]);
+    assert_eq!(res, Err(Error::UnexpectedEnd));
+    assert_eq!(DROPS.swap(0, Ordering::Relaxed), 1);
+
     println!("Passed!");
 }
+
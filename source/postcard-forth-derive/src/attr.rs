@@ -0,0 +1,178 @@
+//! Parsing of the `#[postcard_forth(...)]` attributes
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Attribute, Lit, Meta, NestedMeta, Token, WherePredicate};
+
+/// Attributes on the struct or enum itself
+pub struct Container {
+    /// The path the generated code reaches `postcard_forth` through
+    pub krate: syn::Path,
+    /// Replaces the inferred bounds of the `Serialize` impl
+    pub ser_bound: Option<Vec<WherePredicate>>,
+    /// Replaces the inferred bounds of the `Deserialize` impl
+    pub de_bound: Option<Vec<WherePredicate>>,
+}
+
+impl Container {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Container {
+            krate: parse_quote!(::postcard_forth),
+            ser_bound: None,
+            de_bound: None,
+        };
+        for meta in postcard_forth_metas(attrs)? {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => {
+                    let Lit::Str(s) = &nv.lit else {
+                        return Err(syn::Error::new_spanned(&nv.lit, "expected a string"));
+                    };
+                    out.krate = s.parse()?;
+                }
+                // `bound = "..."` sets both
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("bound") => {
+                    let bound = parse_bound(&nv.lit)?;
+                    out.ser_bound = Some(bound.clone());
+                    out.de_bound = Some(bound);
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("bound") => {
+                    for nested in list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::NameValue(nv))
+                                if nv.path.is_ident("serialize") =>
+                            {
+                                out.ser_bound = Some(parse_bound(&nv.lit)?);
+                            }
+                            NestedMeta::Meta(Meta::NameValue(nv))
+                                if nv.path.is_ident("deserialize") =>
+                            {
+                                out.de_bound = Some(parse_bound(&nv.lit)?);
+                            }
+                            other => {
+                                return Err(syn::Error::new_spanned(
+                                    other,
+                                    "expected `serialize = \"...\"` or `deserialize = \"...\"`",
+                                ))
+                            }
+                        }
+                    }
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "unknown postcard_forth container attribute",
+                    ))
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Attributes on a single field
+#[derive(Default)]
+pub struct Field {
+    /// Left out of the message, and filled in on deserialize
+    pub skip: bool,
+    /// Called to fill in a skipped field, instead of `Default::default`
+    pub default: Option<syn::Path>,
+    /// A module with `ser` and `deser` functions to use instead of the
+    /// field type's impls
+    pub with: Option<syn::Path>,
+}
+
+impl Field {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Field::default();
+        let mut default_attr = None;
+        let mut with_attr = None;
+        for meta in postcard_forth_metas(attrs)? {
+            match meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => out.skip = true,
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("default") => {
+                    let Lit::Str(s) = &nv.lit else {
+                        return Err(syn::Error::new_spanned(&nv.lit, "expected a string"));
+                    };
+                    out.default = Some(s.parse()?);
+                    default_attr = Some(nv);
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("with") => {
+                    let Lit::Str(s) = &nv.lit else {
+                        return Err(syn::Error::new_spanned(&nv.lit, "expected a string"));
+                    };
+                    out.with = Some(s.parse()?);
+                    with_attr = Some(nv);
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "unknown postcard_forth field attribute",
+                    ))
+                }
+            }
+        }
+        match (default_attr, with_attr) {
+            (Some(nv), _) if !out.skip => Err(syn::Error::new_spanned(
+                nv,
+                "`default` is only used along with `skip`",
+            )),
+            (_, Some(nv)) if out.skip => Err(syn::Error::new_spanned(
+                nv,
+                "`with` can't be used along with `skip`",
+            )),
+            _ => Ok(out),
+        }
+    }
+
+    /// The `SerFunc` for this field
+    pub fn ser_func(&self, ty: &syn::Type, krate: &syn::Path) -> TokenStream {
+        match &self.with {
+            Some(module) => quote!(#module::ser),
+            None => quote!(#krate::ser_inliner::<#ty>()),
+        }
+    }
+
+    /// The `DeserFunc` for this field
+    pub fn deser_func(&self, ty: &syn::Type, krate: &syn::Path) -> TokenStream {
+        match &self.with {
+            Some(module) => quote!(#module::deser),
+            None => quote!(#krate::deser_inliner::<'de, #ty>()),
+        }
+    }
+
+    /// The value a skipped field is filled in with
+    pub fn default_value(&self, ty: &syn::Type) -> TokenStream {
+        match &self.default {
+            Some(path) => quote!(#path()),
+            None => quote!(<#ty as ::core::default::Default>::default()),
+        }
+    }
+}
+
+/// The items of every `#[postcard_forth(...)]` attribute, in order
+fn postcard_forth_metas(attrs: &[Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut out = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("postcard_forth")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => out.extend(list.nested),
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "expected `#[postcard_forth(...)]`",
+                ))
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Parse a string of comma separated where predicates. An empty string
+/// gives no bounds at all.
+fn parse_bound(lit: &Lit) -> syn::Result<Vec<WherePredicate>> {
+    let Lit::Str(s) = lit else {
+        return Err(syn::Error::new_spanned(lit, "expected a string"));
+    };
+    let preds =
+        s.parse_with(syn::punctuated::Punctuated::<WherePredicate, Token![,]>::parse_terminated)?;
+    Ok(preds.into_iter().collect())
+}
@@ -1,28 +1,50 @@
 use proc_macro2::{Span, TokenStream};
-use quote::{quote, quote_spanned};
+use quote::{quote, quote_spanned, ToTokens};
 use syn::{
     parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, GenericParam,
-    Generics, ImplGenerics, TypeGenerics, WhereClause,
+    Generics, ImplGenerics, Lifetime, LifetimeDef, TypeGenerics, WhereClause,
 };
 
+use crate::attr::Container;
+
 pub fn do_derive_deserialize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
 
-    let span = input.span();
     let name = input.ident;
 
-    // Add a bound `T: Schema` to every type parameter T.
-    let generics = add_trait_bounds(input.generics);
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let attrs = match Container::from_attrs(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    // Add a bound `T: Deserialize<'de>` to every type parameter T that is
+    // sent, and `Ty: Default` for skipped fields, unless the bounds were given
+    let generics = input.generics;
+    let de_generics = add_de_lifetime(&generics);
+    let de_generics = match attrs.de_bound {
+        Some(bound) => crate::with_bound(de_generics, bound),
+        None => {
+            let krate = &attrs.krate;
+            let bounded = crate::add_trait_bounds(
+                de_generics,
+                &input.data,
+                parse_quote!(#krate::Deserialize<'de>),
+            );
+            crate::add_default_bounds(bounded, &input.data)
+        }
+    };
+    let (impl_generics, _, where_clause) = de_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let turbofish = crate::helper_turbofish(&generics);
 
     let expanded = generate_type(
         &input.data,
-        span,
-        name.to_string(),
         name.clone(),
         impl_generics,
         ty_generics,
+        turbofish,
         where_clause,
+        &attrs.krate,
     )
     .unwrap_or_else(syn::Error::into_compile_error);
 
@@ -41,20 +63,20 @@ pub fn do_derive_deserialize(item: proc_macro::TokenStream) -> proc_macro::Token
 
 fn generate_type(
     data: &Data,
-    span: Span,
-    _name: String,
     tyident: syn::Ident,
     impl_generics: ImplGenerics,
     ty_generics: TypeGenerics,
+    turbofish: TokenStream,
     where_clause: Option<&WhereClause>,
+    krate: &syn::Path,
 ) -> Result<TokenStream, syn::Error> {
     match data {
         Data::Struct(data) => {
-            let ty = generate_struct(tyident.clone(), &data.fields);
+            let ty = generate_struct(&data.fields, krate)?;
 
             let expanded = quote! {
-                unsafe impl #impl_generics ::postcard_forth::Deserialize for #tyident #ty_generics #where_clause {
-                    const FIELDS: &'static [::postcard_forth::DeserField] = &[
+                unsafe impl #impl_generics #krate::Deserialize<'de> for #tyident #ty_generics #where_clause {
+                    const FIELDS: &'static [#krate::DeserField] = &[
                         #ty
                     ];
                 }
@@ -68,144 +90,189 @@ fn generate_type(
             let mut arms = TokenStream::new();
             for (i, var) in data.variants.iter().enumerate() {
                 let ident = &var.ident;
-                let fields = generate_arm(&var.fields, tyident.clone(), ident, i as u32);
+                let fields =
+                    generate_arm(&var.fields, &tyident, &ty_generics, ident, i as u32, krate)?;
                 arms.extend(quote! {
                     #fields
                 });
             }
 
             let out = quote! {
-                #[allow(non_snake_case)]
+                #[allow(non_snake_case, clippy::question_mark)]
                 #[inline]
-                pub unsafe fn #desername_ident(stream: &mut ::postcard_forth::DeserStream, base: core::ptr::NonNull<()>) -> Result<(), ()> {
-                    let mut variant = core::mem::MaybeUninit::<u32>::uninit();
-                    ::postcard_forth::impls::deser_u32(stream, core::ptr::NonNull::from(&mut variant).cast())?;
-                    let variant = variant.assume_init();
-                    match variant {
+                pub unsafe fn #desername_ident #impl_generics (__stream: &mut #krate::DeserStream, __base: core::ptr::NonNull<()>) -> #krate::Result<()> #where_clause {
+                    let mut __variant = core::mem::MaybeUninit::<u32>::uninit();
+                    #krate::impls::deser_u32(__stream, core::ptr::NonNull::from(&mut __variant).cast())?;
+                    let __variant = __variant.assume_init();
+                    match __variant {
                         #arms
-                        _ => return Err(()),
+                        _ => return Err(#krate::Error::BadDiscriminant),
                     }
                     Ok(())
                 }
 
-                unsafe impl ::postcard_forth::Deserialize for #tyident {
-                    const FIELDS: &'static [::postcard_forth::DeserField] = &[::postcard_forth::DeserField {
+                unsafe impl #impl_generics #krate::Deserialize<'de> for #tyident #ty_generics #where_clause {
+                    const FIELDS: &'static [#krate::DeserField] = &[#krate::DeserField {
                         offset: 0,
-                        func: #desername_ident,
+                        func: #desername_ident #turbofish,
+                        drop: #krate::drop_inliner::<#tyident #ty_generics>(),
                     }];
                 }
             };
             Ok(out)
         }
         Data::Union(_) => Err(syn::Error::new(
-            span,
+            tyident.span(),
             "unions are not supported by `postcard::experimental::schema`",
         )),
     }
 }
 
-fn generate_struct(tyname: syn::Ident, fields: &Fields) -> TokenStream {
+fn generate_struct(fields: &Fields, krate: &syn::Path) -> syn::Result<TokenStream> {
     let mut out = TokenStream::new();
+    let attrs = crate::with_field_attrs(fields)?;
 
     match fields {
-        syn::Fields::Named(fields) => {
-            let fields = fields.named.iter().map(|f| {
+        syn::Fields::Named(_) => {
+            let fields = attrs.iter().filter(|(_, a)| !a.skip).map(|(f, a)| {
                 let ty = &f.ty;
+                let func = a.deser_func(ty, krate);
                 let name = &f.ident;
-                let out = quote_spanned!(f.span() => ::postcard_forth::DeserField { offset: ::core::mem::offset_of!(#tyname, #name), func: ::postcard_forth::deser_inliner::<#ty>() });
+                let out = quote_spanned!(f.span() => #krate::DeserField { offset: ::core::mem::offset_of!(Self, #name), func: #func, drop: #krate::drop_inliner::<#ty>() });
                 out
             });
             out.extend(quote! {
-                #( #fields ),*
+                #( #fields, )*
             });
         }
-        syn::Fields::Unnamed(fields) => {
-            let fields = fields.unnamed.iter().enumerate().map(|(i, f)| {
+        syn::Fields::Unnamed(_) => {
+            let fields = attrs.iter().enumerate().filter(|(_, (_, a))| !a.skip).map(|(i, (f, a))| {
                 let ty = &f.ty;
+                let func = a.deser_func(ty, krate);
                 let tupidx = syn::Index::from(i);
-                let out = quote_spanned!(f.span() => ::postcard_forth::DeserField { offset: ::core::mem::offset_of!(#tyname, #tupidx), func: ::postcard_forth::deser_inliner::<#ty>() });
+                let out = quote_spanned!(f.span() => #krate::DeserField { offset: ::core::mem::offset_of!(Self, #tupidx), func: #func, drop: #krate::drop_inliner::<#ty>() });
                 out
             });
             out.extend(quote! {
-                #( #fields ),*
+                #( #fields, )*
             });
         }
         syn::Fields::Unit => {}
     }
-    out
+
+    // Skipped fields go last, and are filled in once everything else has
+    // been read
+    let skipped = attrs
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, a))| a.skip)
+        .map(|(i, (f, a))| {
+            let ty = &f.ty;
+            let member = match &f.ident {
+                Some(name) => quote!(#name),
+                None => {
+                    let tupidx = syn::Index::from(i);
+                    quote!(#tupidx)
+                }
+            };
+            let value = a.default_value(ty);
+            quote_spanned!(f.span() => #krate::DeserField {
+                offset: ::core::mem::offset_of!(Self, #member),
+                func: |_: &mut #krate::DeserStream, base: ::core::ptr::NonNull<()>| {
+                    unsafe { base.cast::<#ty>().as_ptr().write(#value) };
+                    Ok(())
+                },
+                drop: #krate::drop_inliner::<#ty>(),
+            })
+        });
+    out.extend(quote! {
+        #( #skipped, )*
+    });
+    Ok(out)
 }
 
 fn generate_arm(
     fields: &Fields,
-    tyident: syn::Ident,
+    tyident: &syn::Ident,
+    ty_generics: &TypeGenerics,
     varident: &syn::Ident,
     idx: u32,
-) -> TokenStream {
-    match fields {
-        syn::Fields::Named(fields) => {
-            let just_names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    krate: &syn::Path,
+) -> syn::Result<TokenStream> {
+    let attrs = crate::with_field_attrs(fields)?;
+    // Skipped fields aren't read, and are filled in once everything else
+    // has been
+    let values: Vec<_> = attrs
+        .iter()
+        .map(|(f, a)| a.skip.then(|| a.default_value(&f.ty)))
+        .collect();
+    let arm = match fields {
+        syn::Fields::Named(_) => {
+            let sent: Vec<_> = attrs.iter().filter(|(_, a)| !a.skip).collect();
+            let just_names: Vec<_> = sent.iter().map(|(f, _)| &f.ident).collect();
             let just_names = just_names.as_slice();
 
-            let just_tys: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+            let just_tys: Vec<_> = sent.iter().map(|(f, _)| &f.ty).collect();
             let just_tys = just_tys.as_slice();
+            let just_funcs: Vec<_> = sent
+                .iter()
+                .map(|(f, a)| a.deser_func(&f.ty, krate))
+                .collect();
+            let just_funcs = just_funcs.as_slice();
+            let payload = generate_payload(just_names, just_tys, just_funcs, krate);
+
+            let all_names = attrs.iter().map(|(f, _)| &f.ident);
+            let all_values = attrs.iter().zip(values).map(|((f, _), value)| {
+                let name = &f.ident;
+                value.unwrap_or_else(|| quote!(#name.assume_init()))
+            });
 
             quote! {
                 #idx => {
                     // Deserialize the payload
-                    #(
-                        let mut #just_names = core::mem::MaybeUninit::<#just_tys>::uninit();
-                        {
-                            const FUNC: ::postcard_forth::DeserFunc = ::postcard_forth::deser_inliner::<#just_tys>();
-                            if (FUNC)(stream, core::ptr::NonNull::from(&mut #just_names).cast()).is_err() {
-                                return Err(());
-                            }
-                        }
-                    )*
-
-                    base.cast::<#tyident>().as_ptr().write(#tyident :: #varident {
+                    #payload
+
+                    __base.cast::<#tyident #ty_generics>().as_ptr().write(#tyident :: #varident {
                         #(
-                            #just_names: #just_names.assume_init(),
+                            #all_names: #all_values,
                         )*
                     });
                 }
             }
         }
-        syn::Fields::Unnamed(fields) => {
+        syn::Fields::Unnamed(_) => {
             let names = b"abcdefghijklmnopqrstuvwxyz";
 
-            let just_names: Vec<_> = fields
-                .unnamed
-                .iter()
-                .zip(names.iter())
-                .map(|(f, c)| {
-                    let ch = &[*c];
-                    let name = syn::Ident::new(core::str::from_utf8(ch).unwrap(), f.span());
-                    quote_spanned! {f.span() => #name}
-                })
-                .collect();
+            let mut just_names = Vec::new();
+            let mut just_tys = Vec::new();
+            let mut just_funcs = Vec::new();
+            let mut all_values = Vec::new();
+            for (((f, a), c), value) in attrs.iter().zip(names.iter()).zip(values) {
+                if let Some(value) = value {
+                    all_values.push(value);
+                    continue;
+                }
+                let ch = &[*c];
+                let name = syn::Ident::new(core::str::from_utf8(ch).unwrap(), f.span());
+                all_values.push(quote_spanned! {f.span() => #name.assume_init()});
+                just_names.push(quote_spanned! {f.span() => #name});
+                just_tys.push(&f.ty);
+                just_funcs.push(a.deser_func(&f.ty, krate));
+            }
 
             let just_names = just_names.as_slice();
-
-            let just_tys: Vec<_> = fields.unnamed.iter().map(|f| &f.ty).collect();
             let just_tys = just_tys.as_slice();
+            let just_funcs = just_funcs.as_slice();
+            let payload = generate_payload(just_names, just_tys, just_funcs, krate);
 
             quote! {
                 #idx => {
                     // Deserialize the payload
-                    #(
-
-                        let mut #just_names = core::mem::MaybeUninit::<#just_tys>::uninit();
-                        {
-                            const FUNC: ::postcard_forth::DeserFunc = ::postcard_forth::deser_inliner::<#just_tys>();
-                            if (FUNC)(stream, core::ptr::NonNull::from(&mut #just_names).cast()).is_err() {
-                                return Err(());
-                            }
-                        }
-                    )*
-
-                    base.cast::<#tyident>().as_ptr().write(#tyident :: #varident (
+                    #payload
+
+                    __base.cast::<#tyident #ty_generics>().as_ptr().write(#tyident :: #varident (
                         #(
-                            #just_names.assume_init(),
+                            #all_values,
                         )*
                     ));
 
@@ -215,20 +282,58 @@ fn generate_arm(
         syn::Fields::Unit => {
             quote! {
                 #idx => {
-                    base.cast::<#tyident>().as_ptr().write(#tyident :: #varident);
+                    __base.cast::<#tyident #ty_generics>().as_ptr().write(#tyident :: #varident);
                 }
             }
         }
+    };
+    Ok(arm)
+}
+
+/// Deserialize each field of a variant into its own temporary. If a field
+/// fails, the temporaries that were already filled are dropped before
+/// returning the error.
+fn generate_payload<N: ToTokens>(
+    just_names: &[N],
+    just_tys: &[&syn::Type],
+    just_funcs: &[TokenStream],
+    krate: &syn::Path,
+) -> TokenStream {
+    let mut out = TokenStream::new();
+    for (i, ((name, ty), func)) in just_names.iter().zip(just_tys).zip(just_funcs).enumerate() {
+        let done = just_names[..i].iter().rev();
+        out.extend(quote! {
+            let mut #name = core::mem::MaybeUninit::<#ty>::uninit();
+            {
+                let __func: #krate::DeserFunc = const { #func };
+                let __outcome = (__func)(__stream, core::ptr::NonNull::from(&mut #name).cast());
+                if __outcome.is_err() {
+                    #( #done.assume_init_drop(); )*
+                    return __outcome;
+                }
+            }
+        });
     }
+    out
 }
 
-/// Add a bound `T: MaxSize` to every type parameter T.
-fn add_trait_bounds(mut generics: Generics) -> Generics {
-    for param in &mut generics.params {
-        if let GenericParam::Type(ref mut type_param) = *param {
-            type_param
-                .bounds
-                .push(parse_quote!(::postcard_forth::Serialize));
+/// Add the `'de` lifetime of the input, which must outlive every lifetime
+/// of the type. A type that already declares a `'de` keeps it as the input
+/// lifetime instead of getting a second one.
+fn add_de_lifetime(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    let de = Lifetime::new("'de", Span::call_site());
+    let others: Vec<_> = generics
+        .lifetimes()
+        .map(|l| l.lifetime.clone())
+        .filter(|l| *l != de)
+        .collect();
+    match generics.lifetimes_mut().find(|l| l.lifetime == de) {
+        Some(existing) => existing.bounds.extend(others),
+        None => {
+            let mut def = LifetimeDef::new(de);
+            def.bounds.extend(others);
+            generics.params.insert(0, GenericParam::Lifetime(def));
         }
     }
     generics
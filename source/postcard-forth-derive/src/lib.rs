@@ -1,13 +1,148 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{parse_quote, Data, GenericParam, Generics, TypeParamBound, WherePredicate};
+
+mod attr;
 mod de;
+mod max_size;
 mod ser;
 
-/// Derive the `postcard::Schema` trait for a struct or enum.
-#[proc_macro_derive(Serialize)]
+/// Derive `postcard_forth::Serialize` for a struct or enum.
+///
+/// Fields can be marked with `#[postcard_forth(skip)]` to leave them out of
+/// the message, or with `#[postcard_forth(with = "module")]` to encode them
+/// with `module::ser` instead. That function has the shape of a `SerFunc`,
+/// and is handed a pointer to the field.
+///
+/// Type parameters used by fields in the message get a `T: Serialize`
+/// bound. On the struct or enum,
+/// `#[postcard_forth(bound(serialize = "..."))]` replaces those, and
+/// `#[postcard_forth(crate = "path")]` sets the path to `postcard_forth`,
+/// for crates that re-export it. Both are shared with the other derives.
+#[proc_macro_derive(Serialize, attributes(postcard_forth))]
 pub fn derive_serialize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     ser::do_derive_serialize(item)
 }
 
-#[proc_macro_derive(Deserialize)]
+/// Derive `postcard_forth::Deserialize` for a struct or enum.
+///
+/// Skipped fields are filled in with `Default::default()`, or with the
+/// function given in `#[postcard_forth(skip, default = "path")]`. A type
+/// parameter only used by skipped fields gets no `Deserialize` bound, the
+/// field type is bound on `Default` instead when that is used. Fields
+/// marked `#[postcard_forth(with = "module")]` are decoded with
+/// `module::deser`, which has the shape of a `DeserFunc` and must fully
+/// initialize the field when it returns `Ok`.
+#[proc_macro_derive(Deserialize, attributes(postcard_forth))]
 pub fn derive_deserialize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     de::do_derive_deserialize(item)
 }
+
+/// Derive `postcard_forth::MaxSize` for a struct or enum without unbounded
+/// fields.
+///
+/// Fields marked `#[postcard_forth(with = "module")]` take their sizes from
+/// `module::MAX_SIZE` and `module::MIN_SIZE`.
+#[proc_macro_derive(MaxSize, attributes(postcard_forth))]
+pub fn derive_max_size(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    max_size::do_derive_max_size(item)
+}
+
+/// The turbofish for calling a generated `ser_`/`deser_` function. Only type
+/// and const parameters are given, the lifetimes are left to be inferred.
+fn helper_turbofish(generics: &Generics) -> TokenStream {
+    let params: Vec<_> = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(ty) => Some(&ty.ident),
+            GenericParam::Const(c) => Some(&c.ident),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    if params.is_empty() {
+        TokenStream::new()
+    } else {
+        quote!(::<#(#params),*>)
+    }
+}
+
+/// Use the bounds from a `#[postcard_forth(bound = "...")]` attribute
+/// instead of the inferred ones
+fn with_bound(mut generics: Generics, bound: Vec<WherePredicate>) -> Generics {
+    generics.make_where_clause().predicates.extend(bound);
+    generics
+}
+
+/// Add `bound` to every type parameter used by a field that is part of the
+/// message. Parameters only used by skipped fields don't need it.
+fn add_trait_bounds(mut generics: Generics, data: &Data, bound: TypeParamBound) -> Generics {
+    let sent: Vec<_> = all_fields(data)
+        .into_iter()
+        .filter(|(_, a)| !a.skip)
+        .map(|(f, _)| f.ty.to_token_stream())
+        .collect();
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            if sent
+                .iter()
+                .any(|ty| mentions(ty.clone(), &type_param.ident))
+            {
+                type_param.bounds.push(bound.clone());
+            }
+        }
+    }
+    generics
+}
+
+/// Add a `Ty: Default` bound for every skipped field that is filled in with
+/// `Default::default()` and whose type uses a type parameter
+fn add_default_bounds(mut generics: Generics, data: &Data) -> Generics {
+    let params: Vec<_> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let preds: Vec<WherePredicate> = all_fields(data)
+        .into_iter()
+        .filter(|(_, a)| a.skip && a.default.is_none())
+        .map(|(f, _)| &f.ty)
+        .filter(|ty| params.iter().any(|p| mentions(ty.to_token_stream(), p)))
+        .map(|ty| parse_quote!(#ty: ::core::default::Default))
+        .collect();
+    if !preds.is_empty() {
+        generics.make_where_clause().predicates.extend(preds);
+    }
+    generics
+}
+
+/// The fields of every variant, along with their attributes. Bad attributes
+/// are read as no attributes here, and reported when generating the code.
+fn all_fields(data: &Data) -> Vec<(&syn::Field, attr::Field)> {
+    let fields: Vec<&syn::Fields> = match data {
+        Data::Struct(data) => vec![&data.fields],
+        Data::Enum(data) => data.variants.iter().map(|v| &v.fields).collect(),
+        Data::Union(_) => Vec::new(),
+    };
+    fields
+        .into_iter()
+        .flatten()
+        .map(|f| (f, attr::Field::from_attrs(&f.attrs).unwrap_or_default()))
+        .collect()
+}
+
+/// Whether `ident` appears anywhere in `tokens`
+fn mentions(tokens: TokenStream, ident: &syn::Ident) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(i) => i == *ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Each field along with its `#[postcard_forth(...)]` attributes
+fn with_field_attrs(fields: &syn::Fields) -> syn::Result<Vec<(&syn::Field, attr::Field)>> {
+    fields
+        .iter()
+        .map(|f| Ok((f, attr::Field::from_attrs(&f.attrs)?)))
+        .collect()
+}
@@ -0,0 +1,106 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields};
+
+use crate::attr::Container;
+
+pub fn do_derive_max_size(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+
+    let span = input.span();
+    let name = input.ident;
+
+    let attrs = match Container::from_attrs(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let krate = &attrs.krate;
+
+    // Add a bound `T: MaxSize` to every type parameter T that is sent.
+    let generics =
+        crate::add_trait_bounds(input.generics, &input.data, parse_quote!(#krate::MaxSize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let sizes =
+        generate_sizes(&input.data, span, krate).unwrap_or_else(syn::Error::into_compile_error);
+
+    let expanded = quote! {
+        impl #impl_generics #krate::MaxSize for #name #ty_generics #where_clause {
+            #sizes
+        }
+    };
+
+    expanded.into()
+}
+
+fn generate_sizes(data: &Data, span: Span, krate: &syn::Path) -> Result<TokenStream, syn::Error> {
+    match data {
+        Data::Struct(data) => {
+            let (max, min) = sum_fields(&data.fields, krate)?;
+            Ok(quote! {
+                const MAX_SIZE: usize = #max;
+                const MIN_SIZE: usize = #min;
+            })
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Ok(quote! {
+                    const MAX_SIZE: usize = 0;
+                    const MIN_SIZE: usize = 0;
+                });
+            }
+
+            // Each variant is its discriminant as a varint, then its fields
+            let (maxes, mins): (Vec<_>, Vec<_>) = data
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(i, var)| {
+                    let idx = i as u64;
+                    let (max, min) = sum_fields(&var.fields, krate)?;
+                    let tag = quote!(#krate::max_size::varint_size(#idx));
+                    Ok((quote!(#tag + #max), quote!(#tag + #min)))
+                })
+                .collect::<syn::Result<Vec<_>>>()?
+                .into_iter()
+                .unzip();
+
+            Ok(quote! {
+                const MAX_SIZE: usize = {
+                    let mut max = 0;
+                    #( max = #krate::max_size::max(max, #maxes); )*
+                    max
+                };
+                const MIN_SIZE: usize = {
+                    let mut min = usize::MAX;
+                    #( min = #krate::max_size::min(min, #mins); )*
+                    min
+                };
+            })
+        }
+        Data::Union(_) => Err(syn::Error::new(
+            span,
+            "unions are not supported by `postcard_forth::MaxSize`",
+        )),
+    }
+}
+
+/// Returns the sums of the `MAX_SIZE` and `MIN_SIZE` of all fields that
+/// aren't skipped
+fn sum_fields(fields: &Fields, krate: &syn::Path) -> syn::Result<(TokenStream, TokenStream)> {
+    let tys: Vec<_> = crate::with_field_attrs(fields)?
+        .into_iter()
+        .filter(|(_, a)| !a.skip)
+        .map(|(f, a)| (f.span(), &f.ty, a.with))
+        .collect();
+    // A field with its own codec takes its sizes from that module
+    let maxes = tys.iter().map(|(span, ty, with)| match with {
+        Some(module) => quote_spanned!(*span => #module::MAX_SIZE),
+        None => quote_spanned!(*span => <#ty as #krate::MaxSize>::MAX_SIZE),
+    });
+    let mins = tys.iter().map(|(span, ty, with)| match with {
+        Some(module) => quote_spanned!(*span => #module::MIN_SIZE),
+        None => quote_spanned!(*span => <#ty as #krate::MaxSize>::MIN_SIZE),
+    });
+    Ok((quote!(0 #( + #maxes )*), quote!(0 #( + #mins )*)))
+}
@@ -1,28 +1,42 @@
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::{
-    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, GenericParam,
-    Generics, ImplGenerics, TypeGenerics, WhereClause,
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, ImplGenerics,
+    TypeGenerics, WhereClause,
 };
 
+use crate::attr::Container;
+
 pub fn do_derive_serialize(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
 
-    let span = input.span();
     let name = input.ident;
 
-    // Add a bound `T: Schema` to every type parameter T.
-    let generics = add_trait_bounds(input.generics);
+    let attrs = match Container::from_attrs(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    // Add a bound `T: Serialize` to every type parameter T that is sent,
+    // unless the bounds were given
+    let generics = match attrs.ser_bound {
+        Some(bound) => crate::with_bound(input.generics, bound),
+        None => {
+            let krate = &attrs.krate;
+            crate::add_trait_bounds(input.generics, &input.data, parse_quote!(#krate::Serialize))
+        }
+    };
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let turbofish = crate::helper_turbofish(&generics);
 
     let expanded = generate_type(
         &input.data,
-        span,
-        name.to_string(),
         name.clone(),
         impl_generics,
         ty_generics,
+        turbofish,
         where_clause,
+        &attrs.krate,
     )
     .unwrap_or_else(syn::Error::into_compile_error);
 
@@ -31,19 +45,19 @@ pub fn do_derive_serialize(item: proc_macro::TokenStream) -> proc_macro::TokenSt
 
 fn generate_type(
     data: &Data,
-    span: Span,
-    _name: String,
     tyident: syn::Ident,
     impl_generics: ImplGenerics,
     ty_generics: TypeGenerics,
+    turbofish: TokenStream,
     where_clause: Option<&WhereClause>,
+    krate: &syn::Path,
 ) -> Result<TokenStream, syn::Error> {
     match data {
         Data::Struct(data) => {
-            let ty = generate_struct(tyident.clone(), &data.fields);
+            let ty = generate_struct(&data.fields, krate)?;
             Ok(quote! {
-                unsafe impl #impl_generics ::postcard_forth::Serialize for #tyident #ty_generics #where_clause {
-                    const FIELDS: &'static [::postcard_forth::SerField] = &[
+                unsafe impl #impl_generics #krate::Serialize for #tyident #ty_generics #where_clause {
+                    const FIELDS: &'static [#krate::SerField] = &[
                         #ty
                     ];
                 }
@@ -55,60 +69,64 @@ fn generate_type(
             let mut arms = TokenStream::new();
             for (i, var) in data.variants.iter().enumerate() {
                 let ident = &var.ident;
-                let fields = generate_arm(&var.fields, tyident.clone(), ident, i as u32);
+                let fields = generate_arm(&var.fields, &tyident, ident, i as u32, krate)?;
                 arms.extend(quote! {
                     #fields
                 });
             }
 
             let out = quote! {
-                #[allow(non_snake_case)]
+                #[allow(non_snake_case, clippy::question_mark)]
                 #[inline]
-                pub unsafe fn #sername_ident(stream: &mut ::postcard_forth::SerStream, base: core::ptr::NonNull<()>) -> Result<(), ()> {
-                    let eref = base.cast::<#tyident>().as_ref();
-                    match eref {
+                pub unsafe fn #sername_ident #impl_generics (__stream: &mut #krate::SerStream, __base: core::ptr::NonNull<()>) -> #krate::Result<()> #where_clause {
+                    let __eref = __base.cast::<#tyident #ty_generics>().as_ref();
+                    match __eref {
                         #arms
                     }
                 }
 
-                unsafe impl ::postcard_forth::Serialize for #tyident {
-                    const FIELDS: &'static [::postcard_forth::SerField] = &[::postcard_forth::SerField {
+                unsafe impl #impl_generics #krate::Serialize for #tyident #ty_generics #where_clause {
+                    const FIELDS: &'static [#krate::SerField] = &[#krate::SerField {
                         offset: 0,
-                        func: #sername_ident,
+                        func: #sername_ident #turbofish,
                     }];
                 }
             };
             Ok(out)
         }
         Data::Union(_) => Err(syn::Error::new(
-            span,
+            tyident.span(),
             "unions are not supported by `postcard::experimental::schema`",
         )),
     }
 }
 
-fn generate_struct(tyname: syn::Ident, fields: &Fields) -> TokenStream {
+fn generate_struct(fields: &Fields, krate: &syn::Path) -> syn::Result<TokenStream> {
     let mut out = TokenStream::new();
+    let attrs = crate::with_field_attrs(fields)?;
 
     match fields {
-        syn::Fields::Named(fields) => {
-            let fields = fields.named.iter().map(|f| {
+        syn::Fields::Named(_) => {
+            // Skipped fields are left out of the table
+            let fields = attrs.iter().filter(|(_, a)| !a.skip).map(|(f, a)| {
                 let ty = &f.ty;
+                let func = a.ser_func(ty, krate);
                 let name = &f.ident;
 
-                let out = quote_spanned!(f.span() => ::postcard_forth::SerField { offset: ::core::mem::offset_of!(#tyname, #name), func: ::postcard_forth::ser_inliner::<#ty>() });
+                let out = quote_spanned!(f.span() => #krate::SerField { offset: ::core::mem::offset_of!(Self, #name), func: #func });
                 out
             });
             out.extend(quote! {
                 #( #fields ),*
             });
         }
-        syn::Fields::Unnamed(fields) => {
-            let fields = fields.unnamed.iter().enumerate().map(|(i, f)| {
+        syn::Fields::Unnamed(_) => {
+            let fields = attrs.iter().enumerate().filter(|(_, (_, a))| !a.skip).map(|(i, (f, a))| {
                 let ty = &f.ty;
+                let func = a.ser_func(ty, krate);
 
                 let tupidx = syn::Index::from(i);
-                let out = quote_spanned!(f.span() => ::postcard_forth::SerField { offset: ::core::mem::offset_of!(#tyname, #tupidx), func: ::postcard_forth::ser_inliner::<#ty>() });
+                let out = quote_spanned!(f.span() => #krate::SerField { offset: ::core::mem::offset_of!(Self, #tupidx), func: #func });
                 out
             });
             out.extend(quote! {
@@ -117,37 +135,45 @@ fn generate_struct(tyname: syn::Ident, fields: &Fields) -> TokenStream {
         }
         syn::Fields::Unit => {}
     }
-    out
+    Ok(out)
 }
 
 fn generate_arm(
     fields: &Fields,
-    tyident: syn::Ident,
+    tyident: &syn::Ident,
     varident: &syn::Ident,
     idx: u32,
-) -> TokenStream {
-    match fields {
-        syn::Fields::Named(fields) => {
-            let just_names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    krate: &syn::Path,
+) -> syn::Result<TokenStream> {
+    let attrs = crate::with_field_attrs(fields)?;
+    let arm = match fields {
+        syn::Fields::Named(_) => {
+            // Skipped fields aren't bound, and are left out of the message
+            let sent: Vec<_> = attrs.iter().filter(|(_, a)| !a.skip).collect();
+            let just_names: Vec<_> = sent.iter().map(|(f, _)| &f.ident).collect();
             let just_names = just_names.as_slice();
-            let just_tys: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+            let just_tys: Vec<_> = sent.iter().map(|(f, _)| &f.ty).collect();
             let just_tys = just_tys.as_slice();
+            let just_funcs: Vec<_> = sent.iter().map(|(f, a)| a.ser_func(&f.ty, krate)).collect();
+            let just_funcs = just_funcs.as_slice();
 
             quote! {
-                #tyident :: #varident { #(#just_names),* } => {
+                #tyident :: #varident { #(#just_names,)* .. } => {
                     // serialize the discriminant as a u32
-                    let var: u32 = #idx;
-                    if ::postcard_forth::impls::ser_u32(stream, core::ptr::NonNull::from(&var).cast()).is_err() {
-                        return Err(());
+                    let __var: u32 = #idx;
+                    let __outcome = #krate::impls::ser_u32(__stream, core::ptr::NonNull::from(&__var).cast());
+                    if __outcome.is_err() {
+                        return __outcome;
                     }
 
                     // Serialize the payload
                     #(
                         {
-                            const FUNC: ::postcard_forth::SerFunc = ::postcard_forth::ser_inliner::<#just_tys>();
-                            let ptr: core::ptr::NonNull<#just_tys> = core::ptr::NonNull::from(#just_names);
-                            if (FUNC)(stream, ptr.cast()).is_err() {
-                                return Err(());
+                            let __func: #krate::SerFunc = const { #just_funcs };
+                            let __ptr: core::ptr::NonNull<#just_tys> = core::ptr::NonNull::from(#just_names);
+                            let __outcome = (__func)(__stream, __ptr.cast());
+                            if __outcome.is_err() {
+                                return __outcome;
                             }
                         }
                     )*
@@ -157,38 +183,47 @@ fn generate_arm(
                 }
             }
         }
-        syn::Fields::Unnamed(fields) => {
+        syn::Fields::Unnamed(_) => {
             let names = b"abcdefghijklmnopqrstuvwxyz";
 
-            let just_names: Vec<_> = fields
-                .unnamed
-                .iter()
-                .zip(names.iter())
-                .map(|(f, c)| {
-                    let ch = &[*c];
-                    let name = syn::Ident::new(core::str::from_utf8(ch).unwrap(), f.span());
-                    quote_spanned! {f.span() => #name}
-                })
-                .collect();
+            // Skipped fields are matched with `_`
+            let mut pats = Vec::new();
+            let mut just_names = Vec::new();
+            let mut just_tys = Vec::new();
+            let mut just_funcs = Vec::new();
+            for ((f, a), c) in attrs.iter().zip(names.iter()) {
+                if a.skip {
+                    pats.push(quote_spanned! {f.span() => _});
+                    continue;
+                }
+                let ch = &[*c];
+                let name = syn::Ident::new(core::str::from_utf8(ch).unwrap(), f.span());
+                pats.push(quote_spanned! {f.span() => #name});
+                just_names.push(quote_spanned! {f.span() => #name});
+                just_tys.push(&f.ty);
+                just_funcs.push(a.ser_func(&f.ty, krate));
+            }
             let just_names = just_names.as_slice();
-            let just_tys: Vec<_> = fields.unnamed.iter().map(|f| &f.ty).collect();
             let just_tys = just_tys.as_slice();
+            let just_funcs = just_funcs.as_slice();
 
             quote! {
-                #tyident :: #varident ( #(#just_names),* ) => {
+                #tyident :: #varident ( #(#pats),* ) => {
                     // serialize the discriminant as a u32
-                    let var: u32 = #idx;
-                    if ::postcard_forth::impls::ser_u32(stream, core::ptr::NonNull::from(&var).cast()).is_err() {
-                        return Err(());
+                    let __var: u32 = #idx;
+                    let __outcome = #krate::impls::ser_u32(__stream, core::ptr::NonNull::from(&__var).cast());
+                    if __outcome.is_err() {
+                        return __outcome;
                     }
 
                     // Serialize the payload
                     #(
                         {
-                            const FUNC: ::postcard_forth::SerFunc = ::postcard_forth::ser_inliner::<#just_tys>();
-                            let ptr: core::ptr::NonNull<#just_tys> = core::ptr::NonNull::from(#just_names);
-                            if (FUNC)(stream, ptr.cast()).is_err() {
-                                return Err(());
+                            let __func: #krate::SerFunc = const { #just_funcs };
+                            let __ptr: core::ptr::NonNull<#just_tys> = core::ptr::NonNull::from(#just_names);
+                            let __outcome = (__func)(__stream, __ptr.cast());
+                            if __outcome.is_err() {
+                                return __outcome;
                             }
                         }
                     )*
@@ -201,22 +236,11 @@ fn generate_arm(
         syn::Fields::Unit => {
             quote! {
                 #tyident :: #varident => {
-                    let var: u32 = #idx;
-                    ::postcard_forth::impls::ser_u32(stream, core::ptr::NonNull::from(&var).cast())
+                    let __var: u32 = #idx;
+                    #krate::impls::ser_u32(__stream, core::ptr::NonNull::from(&__var).cast())
                 }
             }
         }
-    }
-}
-
-/// Add a bound `T: MaxSize` to every type parameter T.
-fn add_trait_bounds(mut generics: Generics) -> Generics {
-    for param in &mut generics.params {
-        if let GenericParam::Type(ref mut type_param) = *param {
-            type_param
-                .bounds
-                .push(parse_quote!(::postcard_forth::Serialize));
-        }
-    }
-    generics
+    };
+    Ok(arm)
 }
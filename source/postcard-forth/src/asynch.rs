@@ -0,0 +1,192 @@
+//! Async serialization and deserialization over `embedded-io-async`
+//!
+//! [`serialize`] and [`AsyncReader::deserialize`] use the same encoding as
+//! [`to_slice`] and [`from_bytes`](crate::from_bytes), so either side can
+//! talk to the blocking functions and readers. Both sides buffer the whole
+//! message, so the `FIELDS` tables are only ever run over bytes that are
+//! already in memory, and no partly initialized value is held across an
+//! `.await`.
+//!
+//! [`serialize_framed`] and [`AsyncReader::deserialize_framed`] opt in to
+//! sending each message as a frame instead: its length as a varint,
+//! followed by the message itself. Both ends must use the framed functions.
+//! Frames are only decoded once they are complete, and a frame that doesn't
+//! decode or is too long for the buffer can be skipped as a whole.
+
+use core::mem::MaybeUninit;
+
+use embedded_io_async::{Read, Write};
+
+use crate::{
+    deser_fields_ref, from_bytes, take_from_bytes, to_slice, DeserStream, DeserializeOwned, Error,
+    MaxSize, Result, Serialize,
+};
+
+/// Serialize a `T` into `buf`, then write it out to `writer`
+///
+/// If the future is dropped, some prefix of the message may have been
+/// written already.
+pub async fn serialize<T: Serialize, W: Write>(
+    value: &T,
+    writer: &mut W,
+    buf: &mut [u8],
+) -> Result<()> {
+    let used = to_slice(value, buf)?;
+    writer.write_all(used).await.map_err(|_| Error::Io)?;
+    writer.flush().await.map_err(|_| Error::Io)
+}
+
+/// Serialize a `T` into `buf`, then write it out to `writer` as one frame,
+/// prefixed with its length
+///
+/// Frames can only be read back with [`AsyncReader::deserialize_framed`].
+/// If the future is dropped, some prefix of the frame may have been
+/// written already.
+pub async fn serialize_framed<T: Serialize, W: Write>(
+    value: &T,
+    writer: &mut W,
+    buf: &mut [u8],
+) -> Result<()> {
+    let body = to_slice(value, buf)?;
+    let mut header = [0u8; usize::MAX_SIZE];
+    let header = to_slice(&body.len(), &mut header)?;
+    writer.write_all(header).await.map_err(|_| Error::Io)?;
+    writer.write_all(body).await.map_err(|_| Error::Io)?;
+    writer.flush().await.map_err(|_| Error::Io)
+}
+
+/// Reads messages from an [`embedded_io_async::Read`], buffering in a
+/// scratch buffer
+///
+/// The buffer must be able to hold the largest message. Bytes read past the
+/// end of one message are kept for the next.
+pub struct AsyncReader<'b, R: Read> {
+    buf: &'b mut [u8],
+    start: usize,
+    end: usize,
+    /// How much is left of a frame that didn't fit in the buffer, and is
+    /// being thrown away as it arrives
+    skip: usize,
+    reader: R,
+}
+
+impl<'b, R: Read> AsyncReader<'b, R> {
+    pub fn new(reader: R, buf: &'b mut [u8]) -> Self {
+        AsyncReader {
+            buf,
+            start: 0,
+            end: 0,
+            skip: 0,
+            reader,
+        }
+    }
+
+    /// Deserialize the next `T`
+    ///
+    /// Decoding is retried each time more bytes arrive, until the whole `T`
+    /// has been read. This is cancellation-safe: if the future is dropped,
+    /// every byte read so far stays in the buffer for the next call.
+    ///
+    /// If the message doesn't decode, the bytes it was decoded from up to
+    /// the error are thrown away, and the rest stay for the next call. A
+    /// reader that ends partway through the `T` gives an
+    /// [`Error::UnexpectedEnd`].
+    pub async fn deserialize<T: DeserializeOwned>(&mut self) -> Result<T> {
+        loop {
+            self.drop_skipped();
+            if self.skip == 0 {
+                let (outcome, used) = decode::<T>(&self.buf[self.start..self.end]);
+                match outcome {
+                    Err(Error::UnexpectedEnd) => {}
+                    outcome => {
+                        self.start += used;
+                        return outcome;
+                    }
+                }
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Deserialize the next `T` from a frame written by
+    /// [`serialize_framed`]
+    ///
+    /// This waits for the whole frame, then decodes it once. It is
+    /// cancellation-safe like [`Self::deserialize`].
+    ///
+    /// If the frame doesn't decode, or is too long for the buffer, only that
+    /// frame is thrown away and the next call picks up after it. A malformed
+    /// length leaves no way to find the next frame, so everything buffered
+    /// is thrown away then.
+    pub async fn deserialize_framed<T: DeserializeOwned>(&mut self) -> Result<T> {
+        loop {
+            self.drop_skipped();
+            if self.skip == 0 {
+                match take_from_bytes::<usize>(&self.buf[self.start..self.end]) {
+                    Ok((len, rest)) => {
+                        let body = self.end - rest.len();
+                        if len > self.buf.len() - (body - self.start) {
+                            self.start = body;
+                            self.skip = len;
+                            return Err(Error::BufferFull);
+                        }
+                        if len <= rest.len() {
+                            self.start = body + len;
+                            return from_bytes(&self.buf[body..body + len]);
+                        }
+                    }
+                    Err(Error::UnexpectedEnd) => {}
+                    Err(e) => {
+                        self.start = 0;
+                        self.end = 0;
+                        return Err(e);
+                    }
+                }
+            }
+            self.fill().await?;
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Drop whatever has arrived of a frame that was too long
+    fn drop_skipped(&mut self) {
+        let dropped = self.skip.min(self.end - self.start);
+        self.start += dropped;
+        self.skip -= dropped;
+    }
+
+    /// Not enough yet, make room and wait for more
+    async fn fill(&mut self) -> Result<()> {
+        if self.start != 0 {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        if self.end == self.buf.len() {
+            self.end = 0;
+            return Err(Error::BufferFull);
+        }
+        match self.reader.read(&mut self.buf[self.end..]).await {
+            Ok(0) => Err(Error::UnexpectedEnd),
+            Ok(n) => {
+                self.end += n;
+                Ok(())
+            }
+            Err(_) => Err(Error::Io),
+        }
+    }
+}
+
+/// Like [`take_from_bytes`], but also returns how much was read when the
+/// value didn't decode
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> (Result<T>, usize) {
+    let mut desers = DeserStream::from(bytes);
+    let mut out = MaybeUninit::<T>::uninit();
+    // SAFETY: `Deserialize` is an unsafe trait, implementors promise that
+    // `FIELDS` fully initializes `T` when it returns `Ok`.
+    let outcome = unsafe { deser_fields_ref(&mut desers, &mut out).map(|()| out.assume_init()) };
+    (outcome, bytes.len() - desers.remain())
+}
@@ -0,0 +1,131 @@
+//! COBS framing
+//!
+//! Frames are COBS-encoded and end with a `0x00`, as written by
+//! [`to_slice_cobs`](crate::to_slice_cobs) and the
+//! [`Cobs`](crate::flavors::Cobs) flavor.
+
+use crate::{from_bytes_cobs, DeserializeOwned, Error, Result};
+
+/// Decode one COBS frame in place, returning the length of the decoded data
+///
+/// Decoding stops at the first `0x00`, or at the end of `buf`.
+pub fn decode_in_place(buf: &mut [u8]) -> Result<usize> {
+    decode_frame(buf).map(|(decoded, _consumed)| decoded)
+}
+
+/// Like [`decode_in_place`], but also returns how many bytes of `buf` made
+/// up the frame, including the `0x00` at the end if there was one
+pub(crate) fn decode_frame(buf: &mut [u8]) -> Result<(usize, usize)> {
+    let len = buf.len();
+    let mut read = 0;
+    let mut write = 0;
+    while read < len {
+        let code = buf[read];
+        if code == 0 {
+            return Ok((write, read + 1));
+        }
+        read += 1;
+        let n = code as usize - 1;
+        if n > len - read || buf[read..][..n].contains(&0) {
+            return Err(Error::BadCobs);
+        }
+        buf.copy_within(read..read + n, write);
+        write += n;
+        read += n;
+        // Every block but a full one ends in a zero, except the last
+        let last = read == len || buf[read] == 0;
+        if code != 0xFF && !last {
+            buf[write] = 0;
+            write += 1;
+        }
+    }
+    Ok((write, read))
+}
+
+/// Collects bytes until it has a whole COBS frame, then deserializes it
+///
+/// Frames longer than `N` bytes, including the `0x00` at the end, are
+/// dropped, up to and including their `0x00`.
+pub struct CobsAccumulator<const N: usize> {
+    buf: [u8; N],
+    idx: usize,
+    /// Set once the current frame has overflowed, until its `0x00` arrives
+    discarding: bool,
+}
+
+/// The result of [`CobsAccumulator::feed`]
+#[derive(Debug)]
+pub enum FeedResult<'a, T> {
+    /// All of the input was consumed, but no frame was completed yet
+    Consumed,
+    /// The frame didn't fit in the buffer, and was dropped. Reported once
+    /// the `0x00` ending that frame arrives, and holds the input after it.
+    OverFull(&'a [u8]),
+    /// A frame was completed, but it didn't decode. Holds the input after
+    /// the end of that frame.
+    DeserError(&'a [u8]),
+    /// A frame was completed and decoded
+    Success {
+        /// The decoded value
+        data: T,
+        /// The input after the end of the frame
+        remaining: &'a [u8],
+    },
+}
+
+impl<const N: usize> Default for CobsAccumulator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CobsAccumulator<N> {
+    pub const fn new() -> Self {
+        CobsAccumulator {
+            buf: [0; N],
+            idx: 0,
+            discarding: false,
+        }
+    }
+
+    /// Append bytes, stopping after the first completed frame
+    ///
+    /// Feed the input again from whatever is left over until this returns
+    /// [`FeedResult::Consumed`].
+    pub fn feed<'a, T: DeserializeOwned>(&mut self, input: &'a [u8]) -> FeedResult<'a, T> {
+        let Some(pos) = input.iter().position(|b| *b == 0) else {
+            if !self.discarding && !self.extend(input) {
+                self.discarding = true;
+            }
+            return FeedResult::Consumed;
+        };
+        let (frame, rest) = input.split_at(pos + 1);
+        if core::mem::take(&mut self.discarding) || !self.extend(frame) {
+            return FeedResult::OverFull(rest);
+        }
+        let len = self.idx;
+        self.idx = 0;
+        match from_bytes_cobs(&mut self.buf[..len]) {
+            Ok(data) => FeedResult::Success {
+                data,
+                remaining: rest,
+            },
+            Err(_) => FeedResult::DeserError(rest),
+        }
+    }
+
+    /// Append to the buffer, or drop everything if it doesn't fit
+    fn extend(&mut self, input: &[u8]) -> bool {
+        match self.buf.get_mut(self.idx..self.idx + input.len()) {
+            Some(dest) => {
+                dest.copy_from_slice(input);
+                self.idx += input.len();
+                true
+            }
+            None => {
+                self.idx = 0;
+                false
+            }
+        }
+    }
+}
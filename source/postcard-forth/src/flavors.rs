@@ -0,0 +1,330 @@
+//! Output sinks for [`SerStream`]
+//!
+//! By default a [`SerStream`] writes to a single slice, and fails with
+//! [`BufferFull`](crate::Error::BufferFull) once it runs out of room. A [`Flavor`] instead hands
+//! the stream a window to write into, and is asked for a new one whenever
+//! that window fills up. This lets us write to growable buffers, writers,
+//! or to stack [`Transform`]s on top of another flavor.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+
+use crate::{Error, Result, SerStream};
+
+/// A sink that [`SerStream`] writes into, one window at a time
+pub trait Flavor {
+    /// Take the first `used` bytes of the last window, and return a new one.
+    ///
+    /// The first call has a `used` of zero. Returning an empty window
+    /// means that the sink is full.
+    ///
+    /// # Safety
+    ///
+    /// The first `used` bytes of the window returned by the previous call
+    /// must have been initialized.
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]>;
+
+    /// Take the first `used` bytes of the last window, no more will follow.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Flavor::refill`].
+    unsafe fn finish(&mut self, used: usize) -> Result<()>;
+}
+
+/// Treat a scratch buffer as a window
+///
+/// `SerStream` only ever writes initialized bytes, so this can't be used
+/// to de-initialize any of `buf`.
+fn scratch(buf: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), buf.len()) }
+}
+
+/// Writes to a slice, like [`SerStream`] does without a flavor
+///
+/// Handy as the bottom of a stack of other flavors.
+pub struct Slice<'b> {
+    buf: &'b mut [u8],
+    used: usize,
+}
+
+impl<'b> Slice<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Slice { buf, used: 0 }
+    }
+
+    /// Returns the used part of the slice
+    pub fn into_used(self) -> &'b mut [u8] {
+        &mut self.buf[..self.used]
+    }
+}
+
+impl Flavor for Slice<'_> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        // Only the first window is non-empty, after that we're full
+        self.used += used;
+        Ok(scratch(&mut self.buf[self.used..]))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.used += used;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Flavor for Vec<u8> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        self.set_len(self.len() + used);
+        self.reserve(1);
+        Ok(self.spare_capacity_mut())
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.set_len(self.len() + used);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> Flavor for heapless::Vec<u8, N> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        let len = self.len() + used;
+        self.set_len(len);
+        let spare = self.as_mut_ptr().add(len).cast();
+        Ok(core::slice::from_raw_parts_mut(spare, N - len))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.set_len(self.len() + used);
+        Ok(())
+    }
+}
+
+/// Writes to a [`std::io::Write`], in chunks the size of a scratch buffer
+#[cfg(feature = "std")]
+pub struct IoWriter<'b, W: std::io::Write> {
+    buf: &'b mut [u8],
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<'b, W: std::io::Write> IoWriter<'b, W> {
+    pub fn new(writer: W, buf: &'b mut [u8]) -> Self {
+        IoWriter { buf, writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Flavor for IoWriter<'_, W> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        self.writer
+            .write_all(&self.buf[..used])
+            .map_err(|_| Error::Io)?;
+        Ok(scratch(self.buf))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.writer
+            .write_all(&self.buf[..used])
+            .map_err(|_| Error::Io)?;
+        self.writer.flush().map_err(|_| Error::Io)
+    }
+}
+
+/// Counts the bytes written, and throws them away
+pub struct Size<'b> {
+    buf: &'b mut [u8],
+    size: usize,
+}
+
+impl<'b> Size<'b> {
+    /// Any non-empty scratch buffer works, longer ones mean fewer refills
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Size { buf, size: 0 }
+    }
+
+    /// The number of bytes written so far
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Flavor for Size<'_> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        self.size += used;
+        Ok(scratch(self.buf))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.size += used;
+        Ok(())
+    }
+}
+
+/// Something that sits between a [`SerStream`] and another flavor
+///
+/// Used with [`Stacked`], which feeds it the serialized bytes in chunks.
+pub trait Transform {
+    /// Take the next chunk of bytes, and write whatever comes of it to `out`
+    fn write(&mut self, bytes: &[u8], out: &mut SerStream<'_>) -> Result<()>;
+
+    /// Called once all bytes have been written
+    fn finish(&mut self, out: &mut SerStream<'_>) -> Result<()> {
+        let _ = out;
+        Ok(())
+    }
+}
+
+/// A flavor that runs a [`Transform`] on top of another flavor
+///
+/// As the inner stream is a flavor again, these can be stacked as deep as
+/// needed.
+pub struct Stacked<'b, 'a, T: Transform> {
+    buf: &'b mut [u8],
+    out: Option<SerStream<'a>>,
+    transform: T,
+}
+
+impl<'b, 'a, T: Transform> Stacked<'b, 'a, T> {
+    pub fn new(transform: T, inner: &'a mut dyn Flavor, buf: &'b mut [u8]) -> Self {
+        Stacked {
+            buf,
+            out: Some(SerStream::from_flavor(inner)),
+            transform,
+        }
+    }
+
+    pub fn into_transform(self) -> T {
+        self.transform
+    }
+}
+
+impl<T: Transform> Flavor for Stacked<'_, '_, T> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        let Some(out) = self.out.as_mut() else {
+            return Ok(&mut []);
+        };
+        self.transform.write(&self.buf[..used], out)?;
+        Ok(scratch(self.buf))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        let Some(mut out) = self.out.take() else {
+            return Ok(());
+        };
+        self.transform.write(&self.buf[..used], &mut out)?;
+        self.transform.finish(&mut out)?;
+        out.finish()
+    }
+}
+
+/// Feeds everything to a [`core::hash::Hasher`], passing it on unchanged
+pub struct Hash<H: core::hash::Hasher>(pub H);
+
+impl<H: core::hash::Hasher> Transform for Hash<H> {
+    fn write(&mut self, bytes: &[u8], out: &mut SerStream<'_>) -> Result<()> {
+        self.0.write(bytes);
+        out.push_n(bytes)
+    }
+}
+
+/// COBS-encodes everything, and ends the frame with a `0x00`
+///
+/// The flavor's window is the current run of up to 254 non-zero bytes,
+/// so this needs no extra scratch space.
+pub struct Cobs<'a> {
+    run: [u8; 254],
+    len: usize,
+    out: Option<SerStream<'a>>,
+}
+
+impl<'a> Cobs<'a> {
+    pub fn new(inner: &'a mut dyn Flavor) -> Self {
+        Cobs {
+            run: [0; 254],
+            len: 0,
+            out: Some(SerStream::from_flavor(inner)),
+        }
+    }
+
+    /// Write out every block in `run[..total]` that ends with a zero, and
+    /// keep the rest for later
+    fn flush_blocks(&mut self, total: usize) -> Result<()> {
+        let Some(out) = self.out.as_mut() else {
+            return Err(Error::BufferFull);
+        };
+        let mut start = 0;
+        while let Some(pos) = self.run[start..total].iter().position(|b| *b == 0) {
+            out.push_one(pos as u8 + 1)?;
+            out.push_n(&self.run[start..][..pos])?;
+            start += pos + 1;
+        }
+        if total - start == self.run.len() {
+            // A full run goes out as a block with no zero after it
+            out.push_one(0xFF)?;
+            out.push_n(&self.run)?;
+            start = total;
+        }
+        self.run.copy_within(start..total, 0);
+        self.len = total - start;
+        Ok(())
+    }
+}
+
+impl Flavor for Cobs<'_> {
+    unsafe fn refill(&mut self, used: usize) -> Result<&mut [MaybeUninit<u8>]> {
+        self.flush_blocks(self.len + used)?;
+        Ok(scratch(&mut self.run[self.len..]))
+    }
+
+    unsafe fn finish(&mut self, used: usize) -> Result<()> {
+        self.flush_blocks(self.len + used)?;
+        let Some(mut out) = self.out.take() else {
+            return Err(Error::BufferFull);
+        };
+        out.push_one(self.len as u8 + 1)?;
+        out.push_n(&self.run[..self.len])?;
+        out.push_one(0x00)?;
+        out.finish()
+    }
+}
+
+macro_rules! crc_transform {
+    ($name:ident, $width:ty, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// The checksum covers everything written, and is appended as a
+        /// little-endian trailer.
+        #[cfg(feature = "crc")]
+        pub struct $name<'c>(pub crc::Digest<'c, $width>);
+
+        #[cfg(feature = "crc")]
+        impl Transform for $name<'_> {
+            fn write(&mut self, bytes: &[u8], out: &mut SerStream<'_>) -> Result<()> {
+                self.0.update(bytes);
+                out.push_n(bytes)
+            }
+
+            fn finish(&mut self, out: &mut SerStream<'_>) -> Result<()> {
+                out.push_n(&self.0.clone().finalize().to_le_bytes())
+            }
+        }
+    };
+}
+
+crc_transform!(
+    Crc16,
+    u16,
+    "Appends a 16-bit CRC, passing everything else on unchanged"
+);
+crc_transform!(
+    Crc32,
+    u32,
+    "Appends a 32-bit CRC, passing everything else on unchanged"
+);
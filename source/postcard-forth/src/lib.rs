@@ -1,18 +1,152 @@
 #![cfg_attr(not(any(test, feature = "std")), no_std)]
-#![allow(clippy::result_unit_err, clippy::missing_safety_doc)]
+#![allow(clippy::missing_safety_doc)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    string::String,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hash};
+use core::{fmt, marker::PhantomData, mem::MaybeUninit, ptr::NonNull};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+/// The error type for all serialization and deserialization operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The output buffer is full, or a reader's buffer is too small for a
+    /// single field
+    BufferFull,
+    /// The input ended before the value was complete
+    UnexpectedEnd,
+    /// A varint was too long, or did not fit in its type
+    BadVarint,
+    /// A `bool` was not encoded as `0` or `1`
+    BadBool,
+    /// A string was not valid UTF-8
+    BadUtf8,
+    /// An enum discriminant did not match any variant
+    BadDiscriminant,
+    /// An `Option` tag was not encoded as `0` or `1`
+    BadOptionTag,
+    /// The underlying reader or writer returned an error
+    Io,
+    /// A COBS frame was malformed
+    BadCobs,
+    /// A length was longer than the capacity of a fixed-size collection
+    TooLong,
+    /// The CRC trailer did not match the message
+    ChecksumMismatch,
+    /// A `char` was not a single UTF-8 encoded character
+    BadChar,
+    /// A `NonZero*` integer was zero
+    BadNonZero,
+    /// A `Duration` had too many nanoseconds, or a `SystemTime` was out of
+    /// range
+    BadTime,
+    /// Borrowed bytes were asked for from a stream reading from a
+    /// [`Source`](sources::Source), whose buffer is reused
+    NotBorrowable,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::BufferFull => "the buffer is full",
+            Error::UnexpectedEnd => "hit the end of the input before the value was complete",
+            Error::BadVarint => "found a malformed varint",
+            Error::BadBool => "found a bool that wasn't 0 or 1",
+            Error::BadUtf8 => "found a string that wasn't valid UTF-8",
+            Error::BadDiscriminant => "found an enum discriminant with no matching variant",
+            Error::BadOptionTag => "found an Option tag that wasn't 0 or 1",
+            Error::Io => "the underlying reader or writer returned an error",
+            Error::BadCobs => "found a malformed COBS frame",
+            Error::TooLong => "found a length longer than the collection's capacity",
+            Error::ChecksumMismatch => "checksum mismatch",
+            Error::BadChar => "found a char that wasn't a single character",
+            Error::BadNonZero => "found a NonZero integer that was zero",
+            Error::BadTime => "found a time or duration that was out of range",
+            Error::NotBorrowable => "can't borrow from a stream reading from a source",
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// A `Result` with this crate's [`Error`] as the error type
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[cfg(feature = "embedded-io-async")]
+pub mod asynch;
+pub mod cobs;
+pub mod flavors;
+pub mod max_size;
+pub mod sources;
 
-use core::{marker::PhantomData, mem::MaybeUninit, ptr::NonNull};
+pub use max_size::MaxSize;
+
+use flavors::Flavor;
+use sources::Source;
 
 pub struct SerStream<'a> {
     cur: *mut u8,
     end: *mut u8,
+    /// The start of the current window, only used with a flavor
+    start: *mut u8,
+    /// If set, `start..end` is a window handed out by this flavor, which is
+    /// asked for a new one when we run out of room instead of failing.
+    flavor: Option<&'a mut dyn Flavor>,
+    /// If set, nothing is written, and every push only adds its length here.
+    /// The window is always empty, so each push takes the slow path.
+    count: Option<usize>,
     _plt: PhantomData<&'a mut u8>,
 }
 
 impl<'a> SerStream<'a> {
-    pub fn push_one(&mut self, one: u8) -> Result<(), ()> {
+    /// Create a stream that writes to the given flavor.
+    ///
+    /// Call [`Self::finish`] once done, so the flavor receives the last bytes.
+    pub fn from_flavor(flavor: &'a mut dyn Flavor) -> Self {
+        // Start with an empty window, the first push will ask for a real one
+        let base: *mut u8 = NonNull::dangling().as_ptr();
+        SerStream {
+            cur: base,
+            end: base,
+            start: base,
+            flavor: Some(flavor),
+            count: None,
+            _plt: PhantomData,
+        }
+    }
+
+    /// Create a stream that writes nothing, and only counts the bytes pushed
+    fn counting() -> Self {
+        let base: *mut u8 = NonNull::dangling().as_ptr();
+        SerStream {
+            cur: base,
+            end: base,
+            start: base,
+            flavor: None,
+            count: Some(0),
+            _plt: PhantomData,
+        }
+    }
+
+    pub fn push_one(&mut self, one: u8) -> Result<()> {
         if self.cur == self.end {
-            Err(())
+            self.push_one_slow(one)
         } else {
             unsafe { self.cur.write(one) }
             self.cur = self.cur.wrapping_add(1);
@@ -20,11 +154,11 @@ impl<'a> SerStream<'a> {
         }
     }
 
-    pub fn push_n(&mut self, sli: &[u8]) -> Result<(), ()> {
+    pub fn push_n(&mut self, sli: &[u8]) -> Result<()> {
         let remain = self.remain();
         let n = sli.len();
         if n > remain {
-            Err(())
+            self.push_n_slow(sli)
         } else {
             unsafe { core::ptr::copy_nonoverlapping(sli.as_ptr(), self.cur, n) }
             self.cur = self.cur.wrapping_add(n);
@@ -36,6 +170,63 @@ impl<'a> SerStream<'a> {
     pub fn remain(&self) -> usize {
         (self.end as usize) - (self.cur as usize)
     }
+
+    /// Hand the last bytes to the flavor, if any, and tell it no more follow.
+    ///
+    /// This does nothing for streams that write to a slice.
+    pub fn finish(self) -> Result<()> {
+        match self.flavor {
+            // SAFETY: we've written everything up to `cur`
+            Some(flavor) => unsafe { flavor.finish((self.cur as usize) - (self.start as usize)) },
+            None => Ok(()),
+        }
+    }
+
+    #[cold]
+    fn push_one_slow(&mut self, one: u8) -> Result<()> {
+        if let Some(count) = &mut self.count {
+            *count += 1;
+            return Ok(());
+        }
+        self.refill()?;
+        self.push_one(one)
+    }
+
+    #[cold]
+    fn push_n_slow(&mut self, mut sli: &[u8]) -> Result<()> {
+        if let Some(count) = &mut self.count {
+            *count += sli.len();
+            return Ok(());
+        }
+        loop {
+            let (now, later) = sli.split_at(self.remain().min(sli.len()));
+            unsafe { core::ptr::copy_nonoverlapping(now.as_ptr(), self.cur, now.len()) }
+            self.cur = self.cur.wrapping_add(now.len());
+            if later.is_empty() {
+                return Ok(());
+            }
+            self.refill()?;
+            sli = later;
+        }
+    }
+
+    /// Ask the flavor for a new window, if we have one
+    fn refill(&mut self) -> Result<()> {
+        let Some(flavor) = self.flavor.as_deref_mut() else {
+            return Err(Error::BufferFull);
+        };
+        let used = (self.cur as usize) - (self.start as usize);
+        // SAFETY: we've written everything up to `cur`
+        let window = unsafe { flavor.refill(used)? };
+        if window.is_empty() {
+            return Err(Error::BufferFull);
+        }
+        let base: *mut u8 = window.as_mut_ptr().cast();
+        self.start = base;
+        self.cur = base;
+        self.end = base.wrapping_add(window.len());
+        Ok(())
+    }
 }
 
 impl<'a> From<&'a mut [u8]> for SerStream<'a> {
@@ -46,12 +237,15 @@ impl<'a> From<&'a mut [u8]> for SerStream<'a> {
         SerStream {
             cur: base,
             end,
+            start: base,
+            flavor: None,
+            count: None,
             _plt: PhantomData,
         }
     }
 }
 
-pub type SerFunc = unsafe fn(&mut SerStream, NonNull<()>) -> Result<(), ()>;
+pub type SerFunc = unsafe fn(&mut SerStream, NonNull<()>) -> Result<()>;
 pub struct SerField {
     pub offset: usize,
     pub func: SerFunc,
@@ -70,7 +264,7 @@ unsafe fn ser_fields_inner(
     stream: &mut SerStream,
     base: NonNull<()>,
     fields: &'static [SerField],
-) -> Result<(), ()> {
+) -> Result<()> {
     // TODO: Can we leverage something like iterator flattening to force this
     // to be iterative instead of recursive, for better stack usage?
     for field in fields {
@@ -89,30 +283,47 @@ unsafe fn ser_fields_inner(
 /// # Safety
 /// don't mess it up
 #[inline]
-pub unsafe fn ser_fields_ref<S: Serialize>(stream: &mut SerStream, base: &S) -> Result<(), ()> {
+pub unsafe fn ser_fields_ref<S: Serialize>(stream: &mut SerStream, base: &S) -> Result<()> {
     let nn_ptr: NonNull<S> = NonNull::from(base);
     let nn_erased: NonNull<()> = nn_ptr.cast();
     ser_fields_inner(stream, nn_erased, S::FIELDS)
 }
 
 #[inline]
-pub unsafe fn ser_fields<S: Serialize>(
-    stream: &mut SerStream,
-    base: NonNull<()>,
-) -> Result<(), ()> {
+pub unsafe fn ser_fields<S: Serialize>(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
     ser_fields_inner(stream, base, S::FIELDS)
 }
 
 pub struct DeserStream<'a> {
     cur: *const u8,
     end: *const u8,
+    /// The start of the current window, only used with a source
+    start: *const u8,
+    /// If set, `start..end` is a window handed out by this source, which is
+    /// asked for more when we run out of bytes instead of failing.
+    source: Option<&'a mut dyn Source>,
     _plt: PhantomData<&'a u8>,
 }
 
 impl<'a> DeserStream<'a> {
-    pub fn pop_one(&mut self) -> Result<u8, ()> {
+    /// Create a stream that reads from the given source.
+    ///
+    /// Call [`Self::finish`] once done, so the source knows how much was used.
+    pub fn from_source(source: &'a mut dyn Source) -> Self {
+        // Start with an empty window, the first pop will ask for a real one
+        let base: *const u8 = NonNull::dangling().as_ptr();
+        DeserStream {
+            cur: base,
+            end: base,
+            start: base,
+            source: Some(source),
+            _plt: PhantomData,
+        }
+    }
+
+    pub fn pop_one(&mut self) -> Result<u8> {
         if self.cur == self.end {
-            Err(())
+            self.pop_one_slow()
         } else {
             let val = unsafe { self.cur.read() };
             self.cur = self.cur.wrapping_add(1);
@@ -120,10 +331,18 @@ impl<'a> DeserStream<'a> {
         }
     }
 
-    pub fn pop_n(&mut self, n: usize) -> Result<&'a [u8], ()> {
+    /// Take the next `n` bytes, borrowed for as long as the input lives
+    ///
+    /// Only a stream reading from a slice can lend out its input. A stream
+    /// reading from a [`Source`] gives an [`Error::NotBorrowable`] instead,
+    /// use [`Self::pop_n_buffered`] there.
+    pub fn pop_n(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.source.is_some() {
+            return Err(Error::NotBorrowable);
+        }
         let remain = self.remain();
         if n > remain {
-            Err(())
+            Err(Error::UnexpectedEnd)
         } else {
             let sli = unsafe { core::slice::from_raw_parts(self.cur, n) };
             self.cur = self.cur.wrapping_add(n);
@@ -131,10 +350,58 @@ impl<'a> DeserStream<'a> {
         }
     }
 
+    /// Take the next `n` bytes, from a slice or a [`Source`]
+    ///
+    /// The returned slice is only valid until the next pop, as a source may
+    /// reuse its buffer.
+    pub fn pop_n_buffered(&mut self, n: usize) -> Result<&[u8]> {
+        let remain = self.remain();
+        if n > remain {
+            self.refill(n)?;
+        }
+        let sli = unsafe { core::slice::from_raw_parts(self.cur, n) };
+        self.cur = self.cur.wrapping_add(n);
+        Ok(sli)
+    }
+
     #[inline]
     pub fn remain(&self) -> usize {
         (self.end as usize) - (self.cur as usize)
     }
+
+    /// Tell the source, if any, how many bytes were used.
+    ///
+    /// This does nothing for streams that read from a slice.
+    pub fn finish(self) -> Result<()> {
+        match self.source {
+            Some(source) => source.finish((self.cur as usize) - (self.start as usize)),
+            None => Ok(()),
+        }
+    }
+
+    #[cold]
+    fn pop_one_slow(&mut self) -> Result<u8> {
+        self.refill(1)?;
+        self.pop_one()
+    }
+
+    /// Ask the source for a window of at least `min` bytes, if we have one
+    #[cold]
+    fn refill(&mut self, min: usize) -> Result<()> {
+        let Some(source) = self.source.as_deref_mut() else {
+            return Err(Error::UnexpectedEnd);
+        };
+        let used = (self.cur as usize) - (self.start as usize);
+        let window = source.refill(used, min)?;
+        let base = window.as_ptr();
+        self.start = base;
+        self.cur = base;
+        self.end = base.wrapping_add(window.len());
+        if window.len() < min {
+            return Err(Error::UnexpectedEnd);
+        }
+        Ok(())
+    }
 }
 
 impl<'a> From<&'a [u8]> for DeserStream<'a> {
@@ -145,63 +412,94 @@ impl<'a> From<&'a [u8]> for DeserStream<'a> {
         DeserStream {
             cur: base,
             end,
+            start: base,
+            source: None,
             _plt: PhantomData,
         }
     }
 }
 
-pub type DeserFunc = unsafe fn(&mut DeserStream, NonNull<()>) -> Result<(), ()>;
+pub type DeserFunc = unsafe fn(&mut DeserStream, NonNull<()>) -> Result<()>;
+pub type DropFunc = unsafe fn(NonNull<()>);
 pub struct DeserField {
     pub offset: usize,
     pub func: DeserFunc,
+    /// Drops an initialized field in place, used to clean up already decoded
+    /// fields when a later field fails
+    pub drop: DropFunc,
 }
 
 /// # Safety
 /// don't mess it up
-pub unsafe trait Deserialize {
+///
+/// `'de` is the lifetime of the input buffer. A type may only borrow from
+/// the input, like `&'de str` does, if it is tied to `'de`: every field of
+/// `Self` must implement `Deserialize<'de>` for the same `'de`.
+pub unsafe trait Deserialize<'de> {
     const FIELDS: &'static [DeserField];
 }
 
+/// A [`Deserialize`] type that doesn't borrow from the input, needed when
+/// reading from anything other than a slice
+pub trait DeserializeOwned: for<'de> Deserialize<'de> {}
+
+impl<T: for<'de> Deserialize<'de>> DeserializeOwned for T {}
+
 /// # Safety
 /// don't mess it up
+///
+/// On error, every field that was already decoded has been dropped again,
+/// so `base` is left entirely uninitialized.
 #[inline]
 unsafe fn deser_fields_inner(
     stream: &mut DeserStream,
     base: NonNull<()>,
     fields: &'static [DeserField],
-) -> Result<(), ()> {
+) -> Result<()> {
     // TODO: Can we leverage something like iterator flattening to force this
     // to be iterative instead of recursive, for better stack usage?
-    for field in fields {
+    for (idx, field) in fields.iter().enumerate() {
         let fbase =
             unsafe { NonNull::new_unchecked(base.as_ptr().wrapping_byte_add(field.offset)) };
         let outcome = unsafe { (field.func)(stream, fbase) };
         // don't pay Into cost
         #[allow(clippy::question_mark)]
         if outcome.is_err() {
+            drop_fields_inner(base, &fields[..idx]);
             return outcome;
         }
     }
     Ok(())
 }
 
+/// # Safety
+/// All of `fields` must be initialized
+#[cold]
+unsafe fn drop_fields_inner(base: NonNull<()>, fields: &'static [DeserField]) {
+    for field in fields.iter().rev() {
+        let fbase =
+            unsafe { NonNull::new_unchecked(base.as_ptr().wrapping_byte_add(field.offset)) };
+        unsafe { (field.drop)(fbase) };
+    }
+}
+
 /// # Safety
 /// don't mess it up
 #[inline]
-pub unsafe fn deser_fields_ref<D: Deserialize>(
+pub unsafe fn deser_fields_ref<'de, D: Deserialize<'de>>(
     stream: &mut DeserStream,
     base: &mut MaybeUninit<D>,
-) -> Result<(), ()> {
+) -> Result<()> {
     let nn_ptr: NonNull<MaybeUninit<D>> = NonNull::from(base);
     let nn_erased: NonNull<()> = nn_ptr.cast();
     deser_fields_inner(stream, nn_erased, D::FIELDS)
 }
 
 #[inline]
-pub unsafe fn deser_fields<D: Deserialize>(
+pub unsafe fn deser_fields<'de, D: Deserialize<'de>>(
     stream: &mut DeserStream,
     base: NonNull<()>,
-) -> Result<(), ()> {
+) -> Result<()> {
     deser_fields_inner(stream, base, D::FIELDS)
 }
 
@@ -216,7 +514,7 @@ pub const fn ser_inliner<T: Serialize>() -> SerFunc {
     }
 }
 
-pub const fn deser_inliner<T: Deserialize>() -> DeserFunc {
+pub const fn deser_inliner<'de, T: Deserialize<'de>>() -> DeserFunc {
     let fields = T::FIELDS;
     if fields.is_empty() {
         impls::deser_nothing
@@ -227,8 +525,246 @@ pub const fn deser_inliner<T: Deserialize>() -> DeserFunc {
     }
 }
 
+pub const fn drop_inliner<T>() -> DropFunc {
+    if core::mem::needs_drop::<T>() {
+        impls::drop_value::<T>
+    } else {
+        impls::drop_nothing
+    }
+}
+
+/// Serialize a `T` to the given slice, returning the used part of the slice
+#[inline]
+pub fn to_slice<'a, T: Serialize>(value: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8]> {
+    let len = buf.len();
+    let mut sers = SerStream::from(&mut *buf);
+    // SAFETY: `Serialize` is an unsafe trait, implementors promise that
+    // `FIELDS` accurately describes `T`.
+    unsafe {
+        ser_fields_ref(&mut sers, value)?;
+    }
+    let used = len - sers.remain();
+    Ok(&mut buf[..used])
+}
+
+/// Serialize a `T` to the given flavor
+#[inline]
+pub fn to_flavor<T: Serialize>(value: &T, flavor: &mut dyn Flavor) -> Result<()> {
+    let mut sers = SerStream::from_flavor(flavor);
+    // SAFETY: `Serialize` is an unsafe trait, implementors promise that
+    // `FIELDS` accurately describes `T`.
+    unsafe {
+        ser_fields_ref(&mut sers, value)?;
+    }
+    sers.finish()
+}
+
+/// Returns the exact number of bytes `value` serializes to
+///
+/// This walks the value with a stream that writes nothing and only counts
+/// the bytes it is given, so no buffer is needed at all.
+#[inline]
+pub fn serialized_size<T: Serialize>(value: &T) -> Result<usize> {
+    let mut sers = SerStream::counting();
+    // SAFETY: `Serialize` is an unsafe trait, implementors promise that
+    // `FIELDS` accurately describes `T`.
+    unsafe {
+        ser_fields_ref(&mut sers, value)?;
+    }
+    Ok(sers.count.unwrap_or_default())
+}
+
+/// Serialize a `T` to a new `Vec<u8>`
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut vec = Vec::new();
+    to_extend(value, &mut vec)?;
+    Ok(vec)
+}
+
+/// Serialize a `T` to the end of an existing `Vec<u8>`
+///
+/// On error, `vec` is left as it was before the call.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn to_extend<T: Serialize>(value: &T, vec: &mut Vec<u8>) -> Result<()> {
+    let len = vec.len();
+    let outcome = to_flavor(value, vec);
+    if outcome.is_err() {
+        vec.truncate(len);
+    }
+    outcome
+}
+
+/// Serialize a `T` to a [`std::io::Write`], using `buf` as scratch space
+///
+/// Returns the writer once everything has been written and flushed.
+#[cfg(feature = "std")]
+#[inline]
+pub fn to_io<T: Serialize, W: std::io::Write>(value: &T, writer: W, buf: &mut [u8]) -> Result<W> {
+    let mut flavor = flavors::IoWriter::new(writer, buf);
+    to_flavor(value, &mut flavor)?;
+    Ok(flavor.into_inner())
+}
+
+/// Deserialize a `T` from the given slice
+///
+/// Any bytes left over after `T` has been deserialized are ignored,
+/// see [`take_from_bytes`] if they are needed.
+#[inline]
+pub fn from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    take_from_bytes(bytes).map(|(t, _rest)| t)
+}
+
+/// Deserialize a `T` from the given source
+///
+/// Whatever the source holds past the end of the `T` is left for the next
+/// call.
+#[inline]
+pub fn from_source<T: DeserializeOwned>(source: &mut dyn Source) -> Result<T> {
+    let mut desers = DeserStream::from_source(source);
+    let mut out = MaybeUninit::<T>::uninit();
+    // SAFETY: `Deserialize` is an unsafe trait, implementors promise that
+    // `FIELDS` fully initializes `T` when it returns `Ok`.
+    let t = unsafe {
+        let outcome = deser_fields_ref(&mut desers, &mut out);
+        // Let the source know what we used, even if we failed
+        desers.finish()?;
+        outcome?;
+        out.assume_init()
+    };
+    Ok(t)
+}
+
+/// Deserialize a `T` from the given slice, returning the `T` and the
+/// unused remainder of the slice
+#[inline]
+pub fn take_from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<(T, &'de [u8])> {
+    let mut desers = DeserStream::from(bytes);
+    let mut out = MaybeUninit::<T>::uninit();
+    // SAFETY: `Deserialize` is an unsafe trait, implementors promise that
+    // `FIELDS` fully initializes `T` when it returns `Ok`.
+    let t = unsafe {
+        deser_fields_ref(&mut desers, &mut out)?;
+        out.assume_init()
+    };
+    let used = bytes.len() - desers.remain();
+    Ok((t, &bytes[used..]))
+}
+
+/// Serialize a `T` to the given slice as a COBS frame, returning the used
+/// part of the slice
+///
+/// The frame ends with a `0x00`.
+#[inline]
+pub fn to_slice_cobs<'a, T: Serialize>(value: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8]> {
+    let mut slice = flavors::Slice::new(buf);
+    to_flavor(value, &mut flavors::Cobs::new(&mut slice))?;
+    Ok(slice.into_used())
+}
+
+/// Serialize a `T` to a new `Vec<u8>` as a COBS frame
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn to_vec_cobs<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut vec = Vec::new();
+    to_flavor(value, &mut flavors::Cobs::new(&mut vec))?;
+    Ok(vec)
+}
+
+/// Decode a COBS frame in place, then deserialize a `T` from it
+///
+/// Anything after the end of the frame is ignored, see
+/// [`take_from_bytes_cobs`] if it is needed.
+#[inline]
+pub fn from_bytes_cobs<'de, T: Deserialize<'de>>(buf: &'de mut [u8]) -> Result<T> {
+    let len = cobs::decode_in_place(buf)?;
+    let buf: &'de [u8] = buf;
+    from_bytes(&buf[..len])
+}
+
+/// Decode a COBS frame in place, then deserialize a `T` from it, returning
+/// the `T` and whatever followed the frame
+#[inline]
+pub fn take_from_bytes_cobs<'de, T: Deserialize<'de>>(
+    buf: &'de mut [u8],
+) -> Result<(T, &'de mut [u8])> {
+    let (len, used) = cobs::decode_frame(buf)?;
+    let (frame, rest) = buf.split_at_mut(used);
+    let frame: &'de [u8] = frame;
+    let t = from_bytes(&frame[..len])?;
+    Ok((t, rest))
+}
+
+macro_rules! crc_functions {
+    ($width:ty, $to_slice:ident, $to_vec:ident, $from_bytes:ident) => {
+        /// Serialize a `T` to the given slice followed by its CRC, returning
+        /// the used part of the slice
+        ///
+        /// The CRC covers the serialized `T`, and is written little-endian.
+        #[cfg(feature = "crc")]
+        #[inline]
+        pub fn $to_slice<'a, T: Serialize>(
+            value: &T,
+            buf: &'a mut [u8],
+            mut digest: crc::Digest<'_, $width>,
+        ) -> Result<&'a mut [u8]> {
+            let len = to_slice(value, buf)?.len();
+            digest.update(&buf[..len]);
+            let trailer = digest.finalize().to_le_bytes();
+            let end = len + trailer.len();
+            let used = buf.get_mut(..end).ok_or(Error::BufferFull)?;
+            used[len..].copy_from_slice(&trailer);
+            Ok(used)
+        }
+
+        /// Serialize a `T` to a new `Vec<u8>` followed by its CRC
+        #[cfg(all(feature = "crc", feature = "alloc"))]
+        #[inline]
+        pub fn $to_vec<T: Serialize>(
+            value: &T,
+            mut digest: crc::Digest<'_, $width>,
+        ) -> Result<Vec<u8>> {
+            let mut vec = to_vec(value)?;
+            digest.update(&vec);
+            vec.extend_from_slice(&digest.finalize().to_le_bytes());
+            Ok(vec)
+        }
+
+        /// Deserialize a `T` from the given slice, which ends with its CRC
+        ///
+        /// The CRC is checked before anything is deserialized.
+        #[cfg(feature = "crc")]
+        #[inline]
+        pub fn $from_bytes<'de, T: Deserialize<'de>>(
+            bytes: &'de [u8],
+            mut digest: crc::Digest<'_, $width>,
+        ) -> Result<T> {
+            const TRAILER: usize = core::mem::size_of::<$width>();
+            let split = bytes
+                .len()
+                .checked_sub(TRAILER)
+                .ok_or(Error::UnexpectedEnd)?;
+            let (payload, trailer) = bytes.split_at(split);
+            digest.update(payload);
+            if digest.finalize().to_le_bytes() != trailer {
+                return Err(Error::ChecksumMismatch);
+            }
+            from_bytes(payload)
+        }
+    };
+}
+
+crc_functions!(u16, to_slice_crc16, to_vec_crc16, from_bytes_crc16);
+crc_functions!(u32, to_slice_crc32, to_vec_crc32, from_bytes_crc32);
+
 pub mod impls {
-    use core::mem::size_of;
+    #[cfg(feature = "alloc")]
+    use core::ops::Deref;
+    use core::{mem::size_of, time::Duration};
+    #[cfg(feature = "std")]
+    use std::time::SystemTime;
 
     use self::{
         de_varint::{
@@ -236,110 +772,110 @@ pub mod impls {
             try_take_varint_u16, try_take_varint_u32, try_take_varint_u64, try_take_varint_usize,
         },
         ser_varint::{
-            varint_u128, varint_u16, varint_u32, varint_u64, varint_usize,
-            zig_zag_i128, zig_zag_i16, zig_zag_i32, zig_zag_i64,
+            varint_u128, varint_u16, varint_u32, varint_u64, varint_usize, zig_zag_i128,
+            zig_zag_i16, zig_zag_i32, zig_zag_i64,
         },
     };
 
     use super::*;
 
     #[inline]
-    pub unsafe fn ser_nothing(_stream: &mut SerStream, _base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_nothing(_stream: &mut SerStream, _base: NonNull<()>) -> Result<()> {
         Ok(())
     }
 
     #[inline]
-    pub unsafe fn ser_bool(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_bool(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: bool = base.cast::<bool>().as_ptr().read();
         stream.push_one(if val { 0x01 } else { 0x00 })
     }
 
     #[inline]
-    pub unsafe fn ser_u8(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_u8(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: u8 = base.cast::<u8>().as_ptr().read();
         stream.push_one(val)
     }
 
     #[inline]
-    pub unsafe fn ser_u16(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_u16(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: u16 = base.cast::<u16>().as_ptr().read();
         varint_u16(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_u32(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_u32(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: u32 = base.cast::<u32>().as_ptr().read();
         varint_u32(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_u64(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_u64(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: u64 = base.cast::<u64>().as_ptr().read();
         varint_u64(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_u128(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_u128(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: u128 = base.cast::<u128>().as_ptr().read();
         varint_u128(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_usize(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_usize(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: usize = base.cast::<usize>().as_ptr().read();
         varint_usize(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_f32(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_f32(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: f32 = base.cast::<f32>().as_ptr().read();
         let val = val.to_le_bytes();
         stream.push_n(&val)
     }
 
     #[inline]
-    pub unsafe fn ser_f64(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_f64(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: f64 = base.cast::<f64>().as_ptr().read();
         let val = val.to_le_bytes();
         stream.push_n(&val)
     }
 
     #[inline]
-    pub unsafe fn ser_i8(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_i8(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: i8 = base.cast::<i8>().as_ptr().read();
         stream.push_one(val as u8)
     }
 
     #[inline]
-    pub unsafe fn ser_i16(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_i16(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: i16 = base.cast::<i16>().as_ptr().read();
         let val: u16 = zig_zag_i16(val);
         varint_u16(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_i32(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_i32(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: i32 = base.cast::<i32>().as_ptr().read();
         let val: u32 = zig_zag_i32(val);
         varint_u32(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_i64(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_i64(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: i64 = base.cast::<i64>().as_ptr().read();
         let val: u64 = zig_zag_i64(val);
         varint_u64(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_i128(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_i128(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: i128 = base.cast::<i128>().as_ptr().read();
         let val: u128 = zig_zag_i128(val);
         varint_u128(val, stream)
     }
 
     #[inline]
-    pub unsafe fn ser_isize(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_isize(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: isize = base.cast::<isize>().as_ptr().read();
 
         #[cfg(target_pointer_width = "16")]
@@ -354,9 +890,41 @@ pub mod impls {
         varint_usize(val, stream)
     }
 
+    /// Encoded as a string holding just the one character
+    #[inline]
+    pub unsafe fn ser_char(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: char = base.cast::<char>().as_ptr().read();
+        let mut buf = [0u8; 4];
+        let utf = val.encode_utf8(&mut buf);
+        stream.push_one(utf.len() as u8)?;
+        stream.push_n(utf.as_bytes())
+    }
+
+    /// Encoded as the whole seconds, then the nanoseconds
+    #[inline]
+    pub unsafe fn ser_duration(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &Duration = base.cast::<Duration>().as_ref();
+        let secs = val.as_secs();
+        let nanos = val.subsec_nanos();
+        ser_u64(stream, NonNull::from(&secs).cast())?;
+        ser_u32(stream, NonNull::from(&nanos).cast())
+    }
+
+    /// Encoded as the [`Duration`] since the Unix epoch. Times before the
+    /// epoch can't be serialized.
     #[cfg(feature = "std")]
     #[inline]
-    pub unsafe fn ser_string(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_system_time(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &SystemTime = base.cast::<SystemTime>().as_ref();
+        let since = val
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| Error::BadTime)?;
+        ser_duration(stream, NonNull::from(&since).cast())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_string(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let val: &String = base.cast::<String>().as_ref();
         let len = val.len();
         ser_usize(stream, NonNull::from(&len).cast())?;
@@ -364,13 +932,89 @@ pub mod impls {
         stream.push_n(bytes)
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_vec<T: Serialize>(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &Vec<T> = base.cast::<Vec<T>>().as_ref();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        for t in val.iter() {
+            ser_fields_ref(stream, t)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "heapless")]
     #[inline]
-    pub unsafe fn ser_vec<T: Serialize>(
+    pub unsafe fn ser_heapless_string<const N: usize>(
         stream: &mut SerStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
-        let val: &Vec<T> = base.cast::<Vec<T>>().as_ref();
+    ) -> Result<()> {
+        let val: &heapless::String<N> = base.cast::<heapless::String<N>>().as_ref();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        stream.push_n(val.as_bytes())
+    }
+
+    #[cfg(feature = "heapless")]
+    #[inline]
+    pub unsafe fn ser_heapless_vec<T: Serialize, const N: usize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &heapless::Vec<T, N> = base.cast::<heapless::Vec<T, N>>().as_ref();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        for t in val.iter() {
+            ser_fields_ref(stream, t)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn ser_str(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &str = base.cast::<&str>().as_ptr().read();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        stream.push_n(val.as_bytes())
+    }
+
+    #[inline]
+    pub unsafe fn ser_slice<T: Serialize>(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &[T] = base.cast::<&[T]>().as_ptr().read();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        for t in val.iter() {
+            ser_fields_ref(stream, t)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn ser_ref<T: Serialize>(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
+        let val: &T = base.cast::<&T>().as_ptr().read();
+        ser_fields_ref(stream, val)
+    }
+
+    /// Serialize the `T` behind a `Box`, `Rc` or `Arc`
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_deref<P: Deref<Target = T>, T: Serialize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &P = base.cast::<P>().as_ref();
+        ser_fields_ref(stream, &**val)
+    }
+
+    /// Serialize the `[T]` behind a `Box`, `Rc` or `Arc`, like a `Vec<T>`
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_deref_slice<P: Deref<Target = [T]>, T: Serialize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &[T] = base.cast::<P>().as_ref();
         let len = val.len();
         ser_usize(stream, NonNull::from(&len).cast())?;
         for t in val.iter() {
@@ -379,11 +1023,99 @@ pub mod impls {
         Ok(())
     }
 
+    /// Serialize the `str` behind a `Box`, `Rc` or `Arc`, like a `String`
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_deref_str<P: Deref<Target = str>>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &str = base.cast::<P>().as_ref();
+        let len = val.len();
+        ser_usize(stream, NonNull::from(&len).cast())?;
+        stream.push_n(val.as_bytes())
+    }
+
+    /// Serialize the length, then each key followed by its value
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn ser_map_entries<'a, K: Serialize + 'a, V: Serialize + 'a>(
+        stream: &mut SerStream,
+        len: usize,
+        entries: impl Iterator<Item = (&'a K, &'a V)>,
+    ) -> Result<()> {
+        unsafe {
+            ser_usize(stream, NonNull::from(&len).cast())?;
+            for (k, v) in entries {
+                ser_fields_ref(stream, k)?;
+                ser_fields_ref(stream, v)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the length, then each item
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn ser_set_items<'a, T: Serialize + 'a>(
+        stream: &mut SerStream,
+        len: usize,
+        items: impl Iterator<Item = &'a T>,
+    ) -> Result<()> {
+        unsafe {
+            ser_usize(stream, NonNull::from(&len).cast())?;
+            for t in items {
+                ser_fields_ref(stream, t)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_btree_map<K: Serialize, V: Serialize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &BTreeMap<K, V> = base.cast::<BTreeMap<K, V>>().as_ref();
+        ser_map_entries(stream, val.len(), val.iter())
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub unsafe fn ser_hash_map<K: Serialize, V: Serialize, S>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &HashMap<K, V, S> = base.cast::<HashMap<K, V, S>>().as_ref();
+        ser_map_entries(stream, val.len(), val.iter())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn ser_btree_set<T: Serialize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &BTreeSet<T> = base.cast::<BTreeSet<T>>().as_ref();
+        ser_set_items(stream, val.len(), val.iter())
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub unsafe fn ser_hash_set<T: Serialize, S>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &HashSet<T, S> = base.cast::<HashSet<T, S>>().as_ref();
+        ser_set_items(stream, val.len(), val.iter())
+    }
+
     #[inline]
     pub unsafe fn ser_arr<T: Serialize, const N: usize>(
         stream: &mut SerStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
+    ) -> Result<()> {
         let val: &[T; N] = base.cast::<[T; N]>().as_ref();
         for t in val.iter() {
             ser_fields_ref(stream, t)?;
@@ -395,7 +1127,7 @@ pub mod impls {
     pub unsafe fn ser_option<T: Serialize>(
         stream: &mut SerStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
+    ) -> Result<()> {
         let val: &Option<T> = base.cast::<Option<T>>().as_ref();
         let disc = val.is_some();
         ser_bool(stream, NonNull::from(&disc).cast())?;
@@ -406,6 +1138,25 @@ pub mod impls {
         }
     }
 
+    /// Encoded like an enum, with `Ok` as variant 0 and `Err` as variant 1
+    #[inline]
+    pub unsafe fn ser_result<T: Serialize, E: Serialize>(
+        stream: &mut SerStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let val: &core::result::Result<T, E> = base.cast::<core::result::Result<T, E>>().as_ref();
+        match val {
+            Ok(t) => {
+                ser_u32(stream, NonNull::from(&0u32).cast())?;
+                ser_fields_ref(stream, t)
+            }
+            Err(e) => {
+                ser_u32(stream, NonNull::from(&1u32).cast())?;
+                ser_fields_ref(stream, e)
+            }
+        }
+    }
+
     unsafe impl Serialize for bool {
         const FIELDS: &'static [SerField] = &[SerField {
             offset: 0,
@@ -511,40 +1262,153 @@ pub mod impls {
         }];
     }
 
-    #[cfg(feature = "std")]
-    unsafe impl Serialize for String {
+    unsafe impl Serialize for char {
         const FIELDS: &'static [SerField] = &[SerField {
             offset: 0,
-            func: impls::ser_string,
+            func: impls::ser_char,
         }];
     }
 
-    #[cfg(feature = "std")]
-    unsafe impl<T: Serialize> Serialize for Vec<T> {
+    unsafe impl Serialize for Duration {
         const FIELDS: &'static [SerField] = &[SerField {
             offset: 0,
-            func: impls::ser_vec::<T>,
+            func: impls::ser_duration,
         }];
     }
 
-    unsafe impl<T: Serialize, const N: usize> Serialize for [T; N] {
+    #[cfg(feature = "std")]
+    unsafe impl Serialize for SystemTime {
         const FIELDS: &'static [SerField] = &[SerField {
             offset: 0,
-            func: impls::ser_arr::<T, N>,
+            func: impls::ser_system_time,
         }];
     }
 
-    unsafe impl<T: Serialize> Serialize for Option<T> {
+    unsafe impl Serialize for () {
         const FIELDS: &'static [SerField] = &[SerField {
             offset: 0,
-            func: impls::ser_option::<T>,
+            func: impls::ser_nothing,
         }];
     }
 
-    unsafe impl<T: Serialize> Serialize for (T,) {
+    unsafe impl<T: ?Sized> Serialize for PhantomData<T> {
         const FIELDS: &'static [SerField] = &[SerField {
-            offset: core::mem::offset_of!((T,), 0),
-            func: ser_inliner::<T>(),
+            offset: 0,
+            func: impls::ser_nothing,
+        }];
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe impl Serialize for String {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_string,
+        }];
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe impl<T: Serialize> Serialize for Vec<T> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_vec::<T>,
+        }];
+    }
+
+    #[cfg(feature = "heapless")]
+    unsafe impl<const N: usize> Serialize for heapless::String<N> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_heapless_string::<N>,
+        }];
+    }
+
+    #[cfg(feature = "heapless")]
+    unsafe impl<T: Serialize, const N: usize> Serialize for heapless::Vec<T, N> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_heapless_vec::<T, N>,
+        }];
+    }
+
+    unsafe impl Serialize for &str {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_str,
+        }];
+    }
+
+    unsafe impl<T: Serialize> Serialize for &[T] {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_slice::<T>,
+        }];
+    }
+
+    /// Encoded just like `T`
+    unsafe impl<T: Serialize> Serialize for &T {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_ref::<T>,
+        }];
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe impl<K: Serialize, V: Serialize> Serialize for BTreeMap<K, V> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_btree_map::<K, V>,
+        }];
+    }
+
+    #[cfg(feature = "std")]
+    unsafe impl<K: Serialize, V: Serialize, S> Serialize for HashMap<K, V, S> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_hash_map::<K, V, S>,
+        }];
+    }
+
+    #[cfg(feature = "alloc")]
+    unsafe impl<T: Serialize> Serialize for BTreeSet<T> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_btree_set::<T>,
+        }];
+    }
+
+    #[cfg(feature = "std")]
+    unsafe impl<T: Serialize, S> Serialize for HashSet<T, S> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_hash_set::<T, S>,
+        }];
+    }
+
+    unsafe impl<T: Serialize, const N: usize> Serialize for [T; N] {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_arr::<T, N>,
+        }];
+    }
+
+    unsafe impl<T: Serialize> Serialize for Option<T> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_option::<T>,
+        }];
+    }
+
+    unsafe impl<T: Serialize, E: Serialize> Serialize for core::result::Result<T, E> {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: impls::ser_result::<T, E>,
+        }];
+    }
+
+    unsafe impl<T: Serialize> Serialize for (T,) {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: core::mem::offset_of!((T,), 0),
+            func: ser_inliner::<T>(),
         }];
     }
 
@@ -700,203 +1564,279 @@ pub mod impls {
     }
 
     #[inline]
-    pub unsafe fn deser_nothing(_stream: &mut DeserStream, _base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn drop_nothing(_base: NonNull<()>) {}
+
+    #[inline]
+    pub unsafe fn drop_value<T>(base: NonNull<()>) {
+        base.cast::<T>().as_ptr().drop_in_place()
+    }
+
+    #[inline]
+    pub unsafe fn deser_nothing(_stream: &mut DeserStream, _base: NonNull<()>) -> Result<()> {
         Ok(())
     }
 
     #[inline]
-    pub unsafe fn deser_bool(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn deser_bool(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
         match stream.pop_one() {
             Ok(0) => base.cast::<bool>().as_ptr().write(false),
             Ok(1) => base.cast::<bool>().as_ptr().write(true),
-            _ => return Err(()),
+            Ok(_) => return Err(Error::BadBool),
+            Err(e) => return Err(e),
         }
         Ok(())
     }
 
     #[inline]
-    pub unsafe fn deser_u8(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = stream.pop_one() {
-            base.cast::<u8>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_u8(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match stream.pop_one() {
+            Ok(val) => {
+                base.cast::<u8>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_u16(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u16(stream) {
-            base.cast::<u16>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_u16(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u16(stream) {
+            Ok(val) => {
+                base.cast::<u16>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_u32(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u32(stream) {
-            base.cast::<u32>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_u32(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u32(stream) {
+            Ok(val) => {
+                base.cast::<u32>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_u64(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u64(stream) {
-            base.cast::<u64>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_u64(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u64(stream) {
+            Ok(val) => {
+                base.cast::<u64>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_u128(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u128(stream) {
-            base.cast::<u128>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_u128(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u128(stream) {
+            Ok(val) => {
+                base.cast::<u128>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_usize(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_usize(stream) {
-            base.cast::<usize>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_usize(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_usize(stream) {
+            Ok(val) => {
+                base.cast::<usize>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_f32(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(bytes) = stream.pop_n(size_of::<f32>()) {
-            let mut buf = [0u8; size_of::<f32>()];
-            buf.copy_from_slice(bytes);
-            let val = f32::from_le_bytes(buf);
-            base.cast::<f32>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_f32(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match stream.pop_n_buffered(size_of::<f32>()) {
+            Ok(bytes) => {
+                let mut buf = [0u8; size_of::<f32>()];
+                buf.copy_from_slice(bytes);
+                let val = f32::from_le_bytes(buf);
+                base.cast::<f32>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_f64(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(bytes) = stream.pop_n(size_of::<f64>()) {
-            let mut buf = [0u8; size_of::<f64>()];
-            buf.copy_from_slice(bytes);
-            let val = f64::from_le_bytes(buf);
-            base.cast::<f64>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_f64(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match stream.pop_n_buffered(size_of::<f64>()) {
+            Ok(bytes) => {
+                let mut buf = [0u8; size_of::<f64>()];
+                buf.copy_from_slice(bytes);
+                let val = f64::from_le_bytes(buf);
+                base.cast::<f64>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_i8(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = stream.pop_one() {
-            base.cast::<i8>().as_ptr().write(val as i8);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_i8(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match stream.pop_one() {
+            Ok(val) => {
+                base.cast::<i8>().as_ptr().write(val as i8);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_i16(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u16(stream) {
-            let val = de_zig_zag_i16(val);
-            base.cast::<i16>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_i16(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u16(stream) {
+            Ok(val) => {
+                let val = de_zig_zag_i16(val);
+                base.cast::<i16>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_i32(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u32(stream) {
-            let val = de_zig_zag_i32(val);
-            base.cast::<i32>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_i32(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u32(stream) {
+            Ok(val) => {
+                let val = de_zig_zag_i32(val);
+                base.cast::<i32>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_i64(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u64(stream) {
-            let val = de_zig_zag_i64(val);
-            base.cast::<i64>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_i64(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u64(stream) {
+            Ok(val) => {
+                let val = de_zig_zag_i64(val);
+                base.cast::<i64>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_i128(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_u128(stream) {
-            let val = de_zig_zag_i128(val);
-            base.cast::<i128>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_i128(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_u128(stream) {
+            Ok(val) => {
+                let val = de_zig_zag_i128(val);
+                base.cast::<i128>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
         }
     }
 
     #[inline]
-    pub unsafe fn deser_isize(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
-        if let Ok(val) = try_take_varint_usize(stream) {
-            #[cfg(target_pointer_width = "16")]
-            let val = de_zig_zag_i16(val as u16) as isize;
-            #[cfg(target_pointer_width = "32")]
-            let val = de_zig_zag_i32(val as u32) as isize;
-            #[cfg(target_pointer_width = "64")]
-            let val = de_zig_zag_i64(val as u64) as isize;
-            base.cast::<isize>().as_ptr().write(val);
-            Ok(())
-        } else {
-            Err(())
+    pub unsafe fn deser_isize(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        match try_take_varint_usize(stream) {
+            Ok(val) => {
+                #[cfg(target_pointer_width = "16")]
+                let val = de_zig_zag_i16(val as u16) as isize;
+                #[cfg(target_pointer_width = "32")]
+                let val = de_zig_zag_i32(val as u32) as isize;
+                #[cfg(target_pointer_width = "64")]
+                let val = de_zig_zag_i64(val as u64) as isize;
+                base.cast::<isize>().as_ptr().write(val);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    #[inline]
+    pub unsafe fn deser_char(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+        if len > 4 {
+            return Err(Error::BadChar);
+        }
+        let bytes = stream.pop_n_buffered(len)?;
+        let utf = core::str::from_utf8(bytes).map_err(|_| Error::BadUtf8)?;
+        let mut chars = utf.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => base.cast::<char>().as_ptr().write(c),
+            _ => return Err(Error::BadChar),
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_duration(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let mut secs = MaybeUninit::<u64>::uninit();
+        deser_u64(stream, NonNull::from(&mut secs).cast())?;
+        let mut nanos = MaybeUninit::<u32>::uninit();
+        deser_u32(stream, NonNull::from(&mut nanos).cast())?;
+        let nanos = nanos.assume_init();
+        if nanos >= 1_000_000_000 {
+            return Err(Error::BadTime);
         }
+        base.cast::<Duration>()
+            .as_ptr()
+            .write(Duration::new(secs.assume_init(), nanos));
+        Ok(())
     }
 
     #[cfg(feature = "std")]
     #[inline]
-    pub unsafe fn deser_string(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn deser_system_time(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let mut since = MaybeUninit::<Duration>::uninit();
+        deser_duration(stream, NonNull::from(&mut since).cast())?;
+        let time = SystemTime::UNIX_EPOCH
+            .checked_add(since.assume_init())
+            .ok_or(Error::BadTime)?;
+        base.cast::<SystemTime>().as_ptr().write(time);
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn deser_string(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
         let mut len = MaybeUninit::<usize>::uninit();
         deser_usize(stream, NonNull::from(&mut len).cast())?;
         let len = len.assume_init();
-        let bytes = stream.pop_n(len)?;
-        let utf = core::str::from_utf8(bytes).map_err(drop)?;
-        let s = utf.to_string();
+        let bytes = stream.pop_n_buffered(len)?;
+        let utf = core::str::from_utf8(bytes).map_err(|_| Error::BadUtf8)?;
+        let s = String::from(utf);
         base.cast::<String>().as_ptr().write(s);
         Ok(())
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "alloc")]
     #[inline]
-    pub unsafe fn deser_vec<T: Deserialize>(
+    pub unsafe fn deser_vec<'de, T: Deserialize<'de>>(
         stream: &mut DeserStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
+    ) -> Result<()> {
         let mut len = MaybeUninit::<usize>::uninit();
         deser_usize(stream, NonNull::from(&mut len).cast())?;
         let len = len.assume_init();
 
         let mut out = Vec::<T>::with_capacity(len);
-        let elems = out.spare_capacity_mut();
-        for elem in elems.iter_mut().take(len) {
-            deser_fields_ref(stream, elem)?;
+        let elems: *mut T = out.as_mut_ptr();
+        for idx in 0..len {
+            let outcome = deser_fields::<T>(stream, NonNull::new_unchecked(elems.add(idx)).cast());
+            // don't pay Into cost
+            #[allow(clippy::question_mark)]
+            if outcome.is_err() {
+                // Only drop the elements we've already decoded
+                out.set_len(idx);
+                return outcome;
+            }
         }
 
         out.set_len(len);
@@ -904,28 +1844,254 @@ pub mod impls {
         Ok(())
     }
 
+    #[cfg(feature = "heapless")]
     #[inline]
-    pub unsafe fn deser_arr<T: Deserialize, const N: usize>(
+    pub unsafe fn deser_heapless_string<const N: usize>(
         stream: &mut DeserStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
-        let mut cursor: *mut T = base.as_ptr().cast();
-        let end = cursor.wrapping_add(N);
-        while cursor != end {
-            deser_fields::<T>(stream, NonNull::new_unchecked(cursor).cast())?;
-            cursor = cursor.wrapping_add(1);
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+        if len > N {
+            return Err(Error::TooLong);
         }
+        let bytes = stream.pop_n_buffered(len)?;
+        let utf = core::str::from_utf8(bytes).map_err(|_| Error::BadUtf8)?;
+        let mut s = heapless::String::<N>::new();
+        // Can't fail, we checked the length above
+        let _ = s.push_str(utf);
+        base.cast::<heapless::String<N>>().as_ptr().write(s);
         Ok(())
     }
 
+    #[cfg(feature = "heapless")]
     #[inline]
-    pub unsafe fn deser_option<T: Deserialize>(
+    pub unsafe fn deser_heapless_vec<'de, T: Deserialize<'de>, const N: usize>(
         stream: &mut DeserStream,
         base: NonNull<()>,
-    ) -> Result<(), ()> {
-        let mut disc = MaybeUninit::<bool>::uninit();
-        deser_bool(stream, NonNull::from(&mut disc).cast())?;
-        let disc = disc.assume_init();
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+        if len > N {
+            return Err(Error::TooLong);
+        }
+
+        let mut out = heapless::Vec::<T, N>::new();
+        let elems: *mut T = out.as_mut_ptr();
+        for idx in 0..len {
+            let outcome = deser_fields::<T>(stream, NonNull::new_unchecked(elems.add(idx)).cast());
+            // don't pay Into cost
+            #[allow(clippy::question_mark)]
+            if outcome.is_err() {
+                // Only drop the elements we've already decoded
+                out.set_len(idx);
+                return outcome;
+            }
+        }
+
+        out.set_len(len);
+        base.cast::<heapless::Vec<T, N>>().as_ptr().write(out);
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_str(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+        let bytes = stream.pop_n(len)?;
+        let utf = core::str::from_utf8(bytes).map_err(|_| Error::BadUtf8)?;
+        base.cast::<&str>().as_ptr().write(utf);
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_bytes(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+        let bytes = stream.pop_n(len)?;
+        base.cast::<&[u8]>().as_ptr().write(bytes);
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_bytes_arr<const N: usize>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let bytes = stream.pop_n(N)?;
+        let arr: &[u8; N] = &*bytes.as_ptr().cast::<[u8; N]>();
+        base.cast::<&[u8; N]>().as_ptr().write(arr);
+        Ok(())
+    }
+
+    /// Deserialize straight into a new heap allocation, so large or
+    /// recursive values are never built on the stack
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn deser_box<'de, T: Deserialize<'de>>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut out = Box::<T>::new_uninit();
+        deser_fields_ref(stream, &mut *out)?;
+        base.cast::<Box<T>>().as_ptr().write(out.assume_init());
+        Ok(())
+    }
+
+    /// Deserialize a `T`, then convert it into a `P`, such as an `Rc<T>`
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn deser_from<'de, T: Deserialize<'de>, P: From<T>>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut out = MaybeUninit::<T>::uninit();
+        deser_fields_ref(stream, &mut out)?;
+        base.cast::<P>().as_ptr().write(P::from(out.assume_init()));
+        Ok(())
+    }
+
+    /// Deserialize one key and its value. If the value fails, the key is
+    /// dropped.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    unsafe fn deser_map_entry<'de, K: Deserialize<'de>, V: Deserialize<'de>>(
+        stream: &mut DeserStream,
+    ) -> Result<(K, V)> {
+        let mut k = MaybeUninit::<K>::uninit();
+        deser_fields_ref(stream, &mut k)?;
+        let k = k.assume_init();
+        let mut v = MaybeUninit::<V>::uninit();
+        deser_fields_ref(stream, &mut v)?;
+        Ok((k, v.assume_init()))
+    }
+
+    /// Deserialize one item of a set
+    #[cfg(feature = "alloc")]
+    #[inline]
+    unsafe fn deser_set_item<'de, T: Deserialize<'de>>(stream: &mut DeserStream) -> Result<T> {
+        let mut t = MaybeUninit::<T>::uninit();
+        deser_fields_ref(stream, &mut t)?;
+        Ok(t.assume_init())
+    }
+
+    // The maps and sets below are only written to `base` once complete. On
+    // an error, the entries already inserted are dropped along with the
+    // unfinished collection.
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn deser_btree_map<'de, K: Deserialize<'de> + Ord, V: Deserialize<'de>>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+
+        let mut out = BTreeMap::<K, V>::new();
+        for _ in 0..len {
+            let (k, v) = deser_map_entry::<K, V>(stream)?;
+            out.insert(k, v);
+        }
+        base.cast::<BTreeMap<K, V>>().as_ptr().write(out);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub unsafe fn deser_hash_map<
+        'de,
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        S: BuildHasher + Default,
+    >(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+
+        let mut out = HashMap::<K, V, S>::default();
+        for _ in 0..len {
+            let (k, v) = deser_map_entry::<K, V>(stream)?;
+            out.insert(k, v);
+        }
+        base.cast::<HashMap<K, V, S>>().as_ptr().write(out);
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub unsafe fn deser_btree_set<'de, T: Deserialize<'de> + Ord>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+
+        let mut out = BTreeSet::<T>::new();
+        for _ in 0..len {
+            out.insert(deser_set_item::<T>(stream)?);
+        }
+        base.cast::<BTreeSet<T>>().as_ptr().write(out);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub unsafe fn deser_hash_set<'de, T: Deserialize<'de> + Eq + Hash, S: BuildHasher + Default>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut len = MaybeUninit::<usize>::uninit();
+        deser_usize(stream, NonNull::from(&mut len).cast())?;
+        let len = len.assume_init();
+
+        let mut out = HashSet::<T, S>::default();
+        for _ in 0..len {
+            out.insert(deser_set_item::<T>(stream)?);
+        }
+        base.cast::<HashSet<T, S>>().as_ptr().write(out);
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_arr<'de, T: Deserialize<'de>, const N: usize>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let start: *mut T = base.as_ptr().cast();
+        for idx in 0..N {
+            let outcome = deser_fields::<T>(stream, NonNull::new_unchecked(start.add(idx)).cast());
+            // don't pay Into cost
+            #[allow(clippy::question_mark)]
+            if outcome.is_err() {
+                // Only drop the elements we've already decoded
+                core::ptr::slice_from_raw_parts_mut(start, idx).drop_in_place();
+                return outcome;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn deser_option<'de, T: Deserialize<'de>>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let disc = match stream.pop_one() {
+            Ok(0) => false,
+            Ok(1) => true,
+            Ok(_) => return Err(Error::BadOptionTag),
+            Err(e) => return Err(e),
+        };
 
         if disc {
             let mut out = MaybeUninit::<T>::uninit();
@@ -940,303 +2106,643 @@ pub mod impls {
         Ok(())
     }
 
-    unsafe impl Deserialize for bool {
+    #[inline]
+    pub unsafe fn deser_result<'de, T: Deserialize<'de>, E: Deserialize<'de>>(
+        stream: &mut DeserStream,
+        base: NonNull<()>,
+    ) -> Result<()> {
+        let mut disc = MaybeUninit::<u32>::uninit();
+        deser_u32(stream, NonNull::from(&mut disc).cast())?;
+        let out = base.cast::<core::result::Result<T, E>>().as_ptr();
+        match disc.assume_init() {
+            0 => {
+                let mut t = MaybeUninit::<T>::uninit();
+                deser_fields_ref(stream, &mut t)?;
+                out.write(Ok(t.assume_init()));
+            }
+            1 => {
+                let mut e = MaybeUninit::<E>::uninit();
+                deser_fields_ref(stream, &mut e)?;
+                out.write(Err(e.assume_init()));
+            }
+            _ => return Err(Error::BadDiscriminant),
+        }
+        Ok(())
+    }
+
+    unsafe impl<'de> Deserialize<'de> for bool {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
             func: deser_bool,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for u8 {
+    unsafe impl<'de> Deserialize<'de> for u8 {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
             func: deser_u8,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for u16 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_u16,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for u32 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_u32,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for u64 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_u64,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for u16 {
+    unsafe impl<'de> Deserialize<'de> for u128 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_u128,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for usize {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_usize,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for f32 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_f32,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for f64 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_f64,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for i8 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_i8,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for i16 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_i16,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for i32 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_i32,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for i64 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_i64,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for i128 {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_i128,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for isize {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_isize,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for char {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_char,
+            drop: drop_nothing,
+        }];
+    }
+
+    unsafe impl<'de> Deserialize<'de> for Duration {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: 0,
+            func: deser_duration,
+            drop: drop_nothing,
+        }];
+    }
+
+    #[cfg(feature = "std")]
+    unsafe impl<'de> Deserialize<'de> for SystemTime {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_u16,
+            func: deser_system_time,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for u32 {
+    unsafe impl<'de> Deserialize<'de> for () {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_u32,
+            func: deser_nothing,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for u64 {
+    unsafe impl<'de, T: ?Sized> Deserialize<'de> for PhantomData<T> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_u64,
+            func: deser_nothing,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for u128 {
+    #[cfg(feature = "alloc")]
+    unsafe impl<'de> Deserialize<'de> for String {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_u128,
+            func: deser_string,
+            drop: drop_inliner::<String>(),
         }];
     }
 
-    unsafe impl Deserialize for usize {
+    #[cfg(feature = "alloc")]
+    unsafe impl<'de, T: Deserialize<'de>> Deserialize<'de> for Vec<T> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_usize,
+            func: deser_vec::<T>,
+            drop: drop_inliner::<Vec<T>>(),
         }];
     }
 
-    unsafe impl Deserialize for f32 {
+    #[cfg(feature = "heapless")]
+    unsafe impl<'de, const N: usize> Deserialize<'de> for heapless::String<N> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_f32,
+            func: deser_heapless_string::<N>,
+            drop: drop_inliner::<heapless::String<N>>(),
         }];
     }
 
-    unsafe impl Deserialize for f64 {
+    #[cfg(feature = "heapless")]
+    unsafe impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for heapless::Vec<T, N> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_f64,
+            func: deser_heapless_vec::<T, N>,
+            drop: drop_inliner::<heapless::Vec<T, N>>(),
         }];
     }
 
-    unsafe impl Deserialize for i8 {
+    unsafe impl<'de: 'a, 'a> Deserialize<'de> for &'a str {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_i8,
+            func: deser_str,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for i16 {
+    unsafe impl<'de: 'a, 'a> Deserialize<'de> for &'a [u8] {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_i16,
+            func: deser_bytes,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for i32 {
+    /// Encoded just like `[u8; N]`
+    unsafe impl<'de: 'a, 'a, const N: usize> Deserialize<'de> for &'a [u8; N] {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_i32,
+            func: deser_bytes_arr::<N>,
+            drop: drop_nothing,
         }];
     }
 
-    unsafe impl Deserialize for i64 {
+    #[cfg(feature = "alloc")]
+    unsafe impl<'de, K: Deserialize<'de> + Ord, V: Deserialize<'de>> Deserialize<'de>
+        for BTreeMap<K, V>
+    {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_i64,
+            func: deser_btree_map::<K, V>,
+            drop: drop_inliner::<BTreeMap<K, V>>(),
         }];
     }
 
-    unsafe impl Deserialize for i128 {
+    #[cfg(feature = "std")]
+    unsafe impl<'de, K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, S: BuildHasher + Default>
+        Deserialize<'de> for HashMap<K, V, S>
+    {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_i128,
+            func: deser_hash_map::<K, V, S>,
+            drop: drop_inliner::<HashMap<K, V, S>>(),
         }];
     }
 
-    unsafe impl Deserialize for isize {
+    #[cfg(feature = "alloc")]
+    unsafe impl<'de, T: Deserialize<'de> + Ord> Deserialize<'de> for BTreeSet<T> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_isize,
+            func: deser_btree_set::<T>,
+            drop: drop_inliner::<BTreeSet<T>>(),
         }];
     }
 
     #[cfg(feature = "std")]
-    unsafe impl Deserialize for String {
+    unsafe impl<'de, T: Deserialize<'de> + Eq + Hash, S: BuildHasher + Default> Deserialize<'de>
+        for HashSet<T, S>
+    {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_string,
+            func: deser_hash_set::<T, S>,
+            drop: drop_inliner::<HashSet<T, S>>(),
         }];
     }
 
-    #[cfg(feature = "std")]
-    unsafe impl<T: Deserialize> Deserialize for Vec<T> {
+    unsafe impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for [T; N] {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_vec::<T>,
+            func: deser_arr::<T, N>,
+            drop: drop_inliner::<[T; N]>(),
         }];
     }
 
-    unsafe impl<T: Deserialize, const N: usize> Deserialize for [T; N] {
+    unsafe impl<'de, T: Deserialize<'de>> Deserialize<'de> for Option<T> {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_arr::<T, N>,
+            func: deser_option::<T>,
+            drop: drop_inliner::<Option<T>>(),
         }];
     }
 
-    unsafe impl<T: Deserialize> Deserialize for Option<T> {
+    unsafe impl<'de, T: Deserialize<'de>, E: Deserialize<'de>> Deserialize<'de>
+        for core::result::Result<T, E>
+    {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
-            func: deser_option::<T>,
+            func: deser_result::<T, E>,
+            drop: drop_inliner::<core::result::Result<T, E>>(),
         }];
     }
 
-    unsafe impl<T: Deserialize> Deserialize for (T,) {
+    /// `Box`, `Rc` and `Arc` are encoded just like what they point to.
+    /// `[T]` is encoded like a `Vec<T>`, and `str` like a `String`.
+    #[cfg(feature = "alloc")]
+    macro_rules! pointer_impls {
+        ($(#[$attr:meta])* $ptr:ident, $deser:expr) => {
+            $(#[$attr])*
+            unsafe impl<T: Serialize> Serialize for $ptr<T> {
+                const FIELDS: &'static [SerField] = &[SerField {
+                    offset: 0,
+                    func: ser_deref::<$ptr<T>, T>,
+                }];
+            }
+
+            $(#[$attr])*
+            unsafe impl<T: Serialize> Serialize for $ptr<[T]> {
+                const FIELDS: &'static [SerField] = &[SerField {
+                    offset: 0,
+                    func: ser_deref_slice::<$ptr<[T]>, T>,
+                }];
+            }
+
+            $(#[$attr])*
+            unsafe impl Serialize for $ptr<str> {
+                const FIELDS: &'static [SerField] = &[SerField {
+                    offset: 0,
+                    func: ser_deref_str::<$ptr<str>>,
+                }];
+            }
+
+            $(#[$attr])*
+            unsafe impl<'de, T: Deserialize<'de>> Deserialize<'de> for $ptr<T> {
+                const FIELDS: &'static [DeserField] = &[DeserField {
+                    offset: 0,
+                    func: $deser,
+                    drop: drop_inliner::<$ptr<T>>(),
+                }];
+            }
+
+            $(#[$attr])*
+            unsafe impl<'de, T: Deserialize<'de>> Deserialize<'de> for $ptr<[T]> {
+                const FIELDS: &'static [DeserField] = &[DeserField {
+                    offset: 0,
+                    func: deser_from::<Vec<T>, $ptr<[T]>>,
+                    drop: drop_inliner::<$ptr<[T]>>(),
+                }];
+            }
+
+            $(#[$attr])*
+            unsafe impl<'de> Deserialize<'de> for $ptr<str> {
+                const FIELDS: &'static [DeserField] = &[DeserField {
+                    offset: 0,
+                    func: deser_from::<String, $ptr<str>>,
+                    drop: drop_inliner::<$ptr<str>>(),
+                }];
+            }
+        };
+    }
+
+    #[cfg(feature = "alloc")]
+    pointer_impls!(Box, deser_box::<T>);
+    #[cfg(feature = "alloc")]
+    pointer_impls!(Rc, deser_from::<T, Rc<T>>);
+    #[cfg(feature = "alloc")]
+    pointer_impls!(
+        #[cfg(target_has_atomic = "ptr")]
+        Arc,
+        deser_from::<T, Arc<T>>
+    );
+
+    /// `NonZero*` integers are encoded just like the integer they wrap, and
+    /// are checked for zero on the way back in
+    macro_rules! nonzero_impls {
+        ($($nz:ident($int:ty) => $ser:ident, $deser:ident, $deser_nz:ident;)*) => {
+            $(
+                #[inline]
+                pub unsafe fn $deser_nz(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
+                    let mut val = MaybeUninit::<$int>::uninit();
+                    $deser(stream, NonNull::from(&mut val).cast())?;
+                    match core::num::$nz::new(val.assume_init()) {
+                        Some(nz) => base.cast::<core::num::$nz>().as_ptr().write(nz),
+                        None => return Err(Error::BadNonZero),
+                    }
+                    Ok(())
+                }
+
+                unsafe impl Serialize for core::num::$nz {
+                    const FIELDS: &'static [SerField] = &[SerField {
+                        offset: 0,
+                        func: $ser,
+                    }];
+                }
+
+                unsafe impl<'de> Deserialize<'de> for core::num::$nz {
+                    const FIELDS: &'static [DeserField] = &[DeserField {
+                        offset: 0,
+                        func: $deser_nz,
+                        drop: drop_nothing,
+                    }];
+                }
+            )*
+        };
+    }
+
+    nonzero_impls! {
+        NonZeroU8(u8) => ser_u8, deser_u8, deser_nonzero_u8;
+        NonZeroU16(u16) => ser_u16, deser_u16, deser_nonzero_u16;
+        NonZeroU32(u32) => ser_u32, deser_u32, deser_nonzero_u32;
+        NonZeroU64(u64) => ser_u64, deser_u64, deser_nonzero_u64;
+        NonZeroU128(u128) => ser_u128, deser_u128, deser_nonzero_u128;
+        NonZeroUsize(usize) => ser_usize, deser_usize, deser_nonzero_usize;
+        NonZeroI8(i8) => ser_i8, deser_i8, deser_nonzero_i8;
+        NonZeroI16(i16) => ser_i16, deser_i16, deser_nonzero_i16;
+        NonZeroI32(i32) => ser_i32, deser_i32, deser_nonzero_i32;
+        NonZeroI64(i64) => ser_i64, deser_i64, deser_nonzero_i64;
+        NonZeroI128(i128) => ser_i128, deser_i128, deser_nonzero_i128;
+        NonZeroIsize(isize) => ser_isize, deser_isize, deser_nonzero_isize;
+    }
+
+    unsafe impl<'de, T: Deserialize<'de>> Deserialize<'de> for (T,) {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: core::mem::offset_of!((T,), 0),
             func: deser_inliner::<T>(),
+            drop: drop_inliner::<T>(),
         }];
     }
 
-    unsafe impl<T: Deserialize, U: Deserialize> Deserialize for (T, U) {
+    unsafe impl<'de, T: Deserialize<'de>, U: Deserialize<'de>> Deserialize<'de> for (T, U) {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
         ];
     }
 
-    unsafe impl<T: Deserialize, U: Deserialize, V: Deserialize> Deserialize for (T, U, V) {
+    unsafe impl<'de, T: Deserialize<'de>, U: Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de>
+        for (T, U, V)
+    {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U, V), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V), 2),
                 func: deser_inliner::<V>(),
+                drop: drop_inliner::<V>(),
             },
         ];
     }
 
-    unsafe impl<T: Deserialize, U: Deserialize, V: Deserialize, W: Deserialize> Deserialize
-        for (T, U, V, W)
+    unsafe impl<
+            'de,
+            T: Deserialize<'de>,
+            U: Deserialize<'de>,
+            V: Deserialize<'de>,
+            W: Deserialize<'de>,
+        > Deserialize<'de> for (T, U, V, W)
     {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W), 2),
                 func: deser_inliner::<V>(),
+                drop: drop_inliner::<V>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W), 3),
                 func: deser_inliner::<W>(),
+                drop: drop_inliner::<W>(),
             },
         ];
     }
 
-    unsafe impl<T: Deserialize, U: Deserialize, V: Deserialize, W: Deserialize, X: Deserialize>
-        Deserialize for (T, U, V, W, X)
+    unsafe impl<
+            'de,
+            T: Deserialize<'de>,
+            U: Deserialize<'de>,
+            V: Deserialize<'de>,
+            W: Deserialize<'de>,
+            X: Deserialize<'de>,
+        > Deserialize<'de> for (T, U, V, W, X)
     {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X), 2),
                 func: deser_inliner::<V>(),
+                drop: drop_inliner::<V>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X), 3),
                 func: deser_inliner::<W>(),
+                drop: drop_inliner::<W>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X), 4),
                 func: deser_inliner::<X>(),
+                drop: drop_inliner::<X>(),
             },
         ];
     }
 
     unsafe impl<
-            T: Deserialize,
-            U: Deserialize,
-            V: Deserialize,
-            W: Deserialize,
-            X: Deserialize,
-            Y: Deserialize,
-        > Deserialize for (T, U, V, W, X, Y)
+            'de,
+            T: Deserialize<'de>,
+            U: Deserialize<'de>,
+            V: Deserialize<'de>,
+            W: Deserialize<'de>,
+            X: Deserialize<'de>,
+            Y: Deserialize<'de>,
+        > Deserialize<'de> for (T, U, V, W, X, Y)
     {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 2),
                 func: deser_inliner::<V>(),
+                drop: drop_inliner::<V>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 3),
                 func: deser_inliner::<W>(),
+                drop: drop_inliner::<W>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 4),
                 func: deser_inliner::<X>(),
+                drop: drop_inliner::<X>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y), 5),
                 func: deser_inliner::<Y>(),
+                drop: drop_inliner::<Y>(),
             },
         ];
     }
 
     unsafe impl<
-            T: Deserialize,
-            U: Deserialize,
-            V: Deserialize,
-            W: Deserialize,
-            X: Deserialize,
-            Y: Deserialize,
-            Z: Deserialize,
-        > Deserialize for (T, U, V, W, X, Y, Z)
+            'de,
+            T: Deserialize<'de>,
+            U: Deserialize<'de>,
+            V: Deserialize<'de>,
+            W: Deserialize<'de>,
+            X: Deserialize<'de>,
+            Y: Deserialize<'de>,
+            Z: Deserialize<'de>,
+        > Deserialize<'de> for (T, U, V, W, X, Y, Z)
     {
         const FIELDS: &'static [DeserField] = &[
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 0),
                 func: deser_inliner::<T>(),
+                drop: drop_inliner::<T>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 1),
                 func: deser_inliner::<U>(),
+                drop: drop_inliner::<U>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 2),
                 func: deser_inliner::<V>(),
+                drop: drop_inliner::<V>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 3),
                 func: deser_inliner::<W>(),
+                drop: drop_inliner::<W>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 4),
                 func: deser_inliner::<X>(),
+                drop: drop_inliner::<X>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 5),
                 func: deser_inliner::<Y>(),
+                drop: drop_inliner::<Y>(),
             },
             DeserField {
                 offset: core::mem::offset_of!((T, U, V, W, X, Y, Z), 6),
                 func: deser_inliner::<Z>(),
+                drop: drop_inliner::<Z>(),
             },
         ];
     }
@@ -1245,7 +2751,7 @@ pub mod impls {
 pub(crate) mod ser_varint {
     // copy and paste from postcard
 
-    use crate::SerStream;
+    use crate::{Result, SerStream};
 
     /// Returns the maximum number of bytes required to encode T.
     pub const fn varint_max<T: Sized>() -> usize {
@@ -1265,7 +2771,7 @@ pub(crate) mod ser_varint {
     }
 
     #[inline]
-    pub fn varint_usize(mut value: usize, out: &mut SerStream) -> Result<(), ()> {
+    pub fn varint_usize(mut value: usize, out: &mut SerStream) -> Result<()> {
         loop {
             let now = value.to_le_bytes()[0];
             if value < 128 {
@@ -1279,7 +2785,7 @@ pub(crate) mod ser_varint {
     }
 
     #[inline]
-    pub fn varint_u16(mut value: u16, out: &mut SerStream) -> Result<(), ()> {
+    pub fn varint_u16(mut value: u16, out: &mut SerStream) -> Result<()> {
         loop {
             let now = value.to_le_bytes()[0];
             if value < 128 {
@@ -1293,7 +2799,7 @@ pub(crate) mod ser_varint {
     }
 
     #[inline]
-    pub fn varint_u32(mut value: u32, out: &mut SerStream) -> Result<(), ()> {
+    pub fn varint_u32(mut value: u32, out: &mut SerStream) -> Result<()> {
         loop {
             let now = value.to_le_bytes()[0];
             if value < 128 {
@@ -1307,7 +2813,7 @@ pub(crate) mod ser_varint {
     }
 
     #[inline]
-    pub fn varint_u64(mut value: u64, out: &mut SerStream) -> Result<(), ()> {
+    pub fn varint_u64(mut value: u64, out: &mut SerStream) -> Result<()> {
         loop {
             let now = value.to_le_bytes()[0];
             if value < 128 {
@@ -1321,7 +2827,7 @@ pub(crate) mod ser_varint {
     }
 
     #[inline]
-    pub fn varint_u128(mut value: u128, out: &mut SerStream) -> Result<(), ()> {
+    pub fn varint_u128(mut value: u128, out: &mut SerStream) -> Result<()> {
         loop {
             let now = value.to_le_bytes()[0];
             if value < 128 {
@@ -1354,7 +2860,7 @@ pub(crate) mod ser_varint {
 mod de_varint {
     // copy and paste from postcard
 
-    use crate::{ser_varint::varint_max, DeserStream};
+    use crate::{ser_varint::varint_max, DeserStream, Error, Result};
 
     /// Returns the maximum value stored in the last encoded byte.
     pub const fn max_of_last_byte<T: Sized>() -> u8 {
@@ -1381,24 +2887,24 @@ mod de_varint {
 
     #[cfg(target_pointer_width = "16")]
     #[inline(always)]
-    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize, ()> {
+    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize> {
         try_take_varint_u16(data).map(|u| u as usize)
     }
 
     #[cfg(target_pointer_width = "32")]
     #[inline(always)]
-    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize, ()> {
+    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize> {
         try_take_varint_u32(data).map(|u| u as usize)
     }
 
     #[cfg(target_pointer_width = "64")]
     #[inline(always)]
-    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize, ()> {
+    pub fn try_take_varint_usize(data: &mut DeserStream) -> Result<usize> {
         try_take_varint_u64(data).map(|u| u as usize)
     }
 
     #[inline]
-    pub fn try_take_varint_u16(data: &mut DeserStream) -> Result<u16, ()> {
+    pub fn try_take_varint_u16(data: &mut DeserStream) -> Result<u16> {
         let mut out = 0;
         for i in 0..varint_max::<u16>() {
             let val = data.pop_one()?;
@@ -1407,17 +2913,17 @@ mod de_varint {
 
             if (val & 0x80) == 0 {
                 if i == varint_max::<u16>() - 1 && val > max_of_last_byte::<u16>() {
-                    return Err(());
+                    return Err(Error::BadVarint);
                 } else {
                     return Ok(out);
                 }
             }
         }
-        Err(())
+        Err(Error::BadVarint)
     }
 
     #[inline]
-    pub fn try_take_varint_u32(data: &mut DeserStream) -> Result<u32, ()> {
+    pub fn try_take_varint_u32(data: &mut DeserStream) -> Result<u32> {
         let mut out = 0;
         for i in 0..varint_max::<u32>() {
             let val = data.pop_one()?;
@@ -1426,17 +2932,17 @@ mod de_varint {
 
             if (val & 0x80) == 0 {
                 if i == varint_max::<u32>() - 1 && val > max_of_last_byte::<u32>() {
-                    return Err(());
+                    return Err(Error::BadVarint);
                 } else {
                     return Ok(out);
                 }
             }
         }
-        Err(())
+        Err(Error::BadVarint)
     }
 
     #[inline]
-    pub fn try_take_varint_u64(data: &mut DeserStream) -> Result<u64, ()> {
+    pub fn try_take_varint_u64(data: &mut DeserStream) -> Result<u64> {
         let mut out = 0;
         for i in 0..varint_max::<u64>() {
             let val = data.pop_one()?;
@@ -1445,17 +2951,17 @@ mod de_varint {
 
             if (val & 0x80) == 0 {
                 if i == varint_max::<u64>() - 1 && val > max_of_last_byte::<u64>() {
-                    return Err(());
+                    return Err(Error::BadVarint);
                 } else {
                     return Ok(out);
                 }
             }
         }
-        Err(())
+        Err(Error::BadVarint)
     }
 
     #[inline]
-    pub fn try_take_varint_u128(data: &mut DeserStream) -> Result<u128, ()> {
+    pub fn try_take_varint_u128(data: &mut DeserStream) -> Result<u128> {
         let mut out = 0;
         for i in 0..varint_max::<u128>() {
             let val = data.pop_one()?;
@@ -1464,20 +2970,23 @@ mod de_varint {
 
             if (val & 0x80) == 0 {
                 if i == varint_max::<u128>() - 1 && val > max_of_last_byte::<u128>() {
-                    return Err(());
+                    return Err(Error::BadVarint);
                 } else {
                     return Ok(out);
                 }
             }
         }
-        Err(())
+        Err(Error::BadVarint)
     }
 }
 
 #[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
-    use core::mem::offset_of;
+    use core::{
+        mem::offset_of,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
     #[derive(Debug, PartialEq)]
     struct Alpha {
@@ -1512,7 +3021,7 @@ mod test {
     //
 
     #[inline]
-    pub unsafe fn ser_dolsot(stream: &mut SerStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn ser_dolsot(stream: &mut SerStream, base: NonNull<()>) -> Result<()> {
         let eref = base.cast::<Dolsot>().as_ref();
         let (var, fun, valref): (u32, SerFunc, NonNull<()>) = match eref {
             Dolsot::Bib(x) => (0, ser_fields::<Alpha>, NonNull::from(x).cast::<()>()),
@@ -1522,8 +3031,11 @@ mod test {
         };
 
         // serialize the discriminant as a u32
-        if impls::ser_u32(stream, NonNull::from(&var).cast()).is_err() {
-            return Err(());
+        let outcome = impls::ser_u32(stream, NonNull::from(&var).cast());
+        // don't pay Into cost
+        #[allow(clippy::question_mark)]
+        if outcome.is_err() {
+            return outcome;
         }
 
         // Serialize the payload
@@ -1531,11 +3043,14 @@ mod test {
     }
 
     #[inline]
-    pub unsafe fn deser_dolsot(stream: &mut DeserStream, base: NonNull<()>) -> Result<(), ()> {
+    pub unsafe fn deser_dolsot(stream: &mut DeserStream, base: NonNull<()>) -> Result<()> {
         let mut disc = MaybeUninit::<u32>::uninit();
         let dolsot_ref = base.cast::<Dolsot>();
-        if impls::deser_u32(stream, NonNull::from(&mut disc).cast()).is_err() {
-            return Err(());
+        let outcome = impls::deser_u32(stream, NonNull::from(&mut disc).cast());
+        // don't pay Into cost
+        #[allow(clippy::question_mark)]
+        if outcome.is_err() {
+            return outcome;
         }
         let disc = disc.assume_init();
         match disc {
@@ -1558,7 +3073,7 @@ mod test {
                 dolsot_ref.as_ptr().write(Dolsot::Bap(val.assume_init()));
             }
             3 => dolsot_ref.as_ptr().write(Dolsot::Bowl),
-            _ => return Err(()),
+            _ => return Err(Error::BadDiscriminant),
         }
         Ok(())
     }
@@ -1625,64 +3140,76 @@ mod test {
         ];
     }
 
-    unsafe impl Deserialize for Alpha {
+    unsafe impl<'de> Deserialize<'de> for Alpha {
         const FIELDS: &'static [DeserField] = &[
             // TODO: It would be possibly more efficient to directly call the various `deser_xx` functions here
             // rather than using the monomorphized handler when we KNOW we have a primitive type
             DeserField {
                 offset: offset_of!(Alpha, a),
                 func: deser_fields::<u8>,
+                drop: drop_inliner::<u8>(),
             },
             DeserField {
                 offset: offset_of!(Alpha, b),
                 func: deser_fields::<u16>,
+                drop: drop_inliner::<u16>(),
             },
             DeserField {
                 offset: offset_of!(Alpha, c),
                 func: deser_fields::<u32>,
+                drop: drop_inliner::<u32>(),
             },
             DeserField {
                 offset: offset_of!(Alpha, d),
                 func: deser_fields::<i8>,
+                drop: drop_inliner::<i8>(),
             },
             DeserField {
                 offset: offset_of!(Alpha, e),
                 func: deser_fields::<i16>,
+                drop: drop_inliner::<i16>(),
             },
             DeserField {
                 offset: offset_of!(Alpha, f),
                 func: deser_fields::<i32>,
+                drop: drop_inliner::<i32>(),
             },
         ];
     }
 
-    unsafe impl Deserialize for Beta {
+    unsafe impl<'de> Deserialize<'de> for Beta {
         const FIELDS: &'static [DeserField] = &[
             // This is a cross check that the native `ser_xx` functions are the same as calling
             // deser_fields even for primitives
             DeserField {
                 offset: offset_of!(Beta, a),
                 func: deser_inliner::<u8>(),
+                drop: drop_inliner::<u8>(),
             },
             DeserField {
                 offset: offset_of!(Beta, b),
                 func: deser_inliner::<u16>(),
+                drop: drop_inliner::<u16>(),
             },
             DeserField {
                 offset: offset_of!(Beta, c),
                 func: deser_inliner::<u32>(),
+                drop: drop_inliner::<u32>(),
             },
             DeserField {
                 offset: offset_of!(Beta, d),
                 func: deser_inliner::<i8>(),
+                drop: drop_inliner::<i8>(),
             },
             DeserField {
                 offset: offset_of!(Beta, e),
                 func: deser_inliner::<i16>(),
+                drop: drop_inliner::<i16>(),
             },
             DeserField {
                 offset: offset_of!(Beta, f),
                 func: deser_inliner::<i32>(),
+                drop: drop_inliner::<i32>(),
             },
         ];
     }
@@ -1694,10 +3221,11 @@ mod test {
         }];
     }
 
-    unsafe impl Deserialize for Dolsot {
+    unsafe impl<'de> Deserialize<'de> for Dolsot {
         const FIELDS: &'static [DeserField] = &[DeserField {
             offset: 0,
             func: deser_dolsot,
+            drop: drop_inliner::<Dolsot>(),
         }];
     }
 
@@ -1705,6 +3233,40 @@ mod test {
     //
     // END OF MACRO GENERATION
 
+    /// Writes a few bytes, then fails
+    struct BadSer;
+
+    unsafe fn ser_bad(stream: &mut SerStream, _base: NonNull<()>) -> Result<()> {
+        stream.push_n(&[1; 100])?;
+        Err(Error::BufferFull)
+    }
+
+    unsafe impl Serialize for BadSer {
+        const FIELDS: &'static [SerField] = &[SerField {
+            offset: 0,
+            func: ser_bad,
+        }];
+    }
+
+    static TRACKED_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    struct Tracked(u8);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            TRACKED_DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    unsafe impl<'de> Deserialize<'de> for Tracked {
+        const FIELDS: &'static [DeserField] = &[DeserField {
+            offset: offset_of!(Tracked, 0),
+            func: deser_inliner::<u8>(),
+            drop: drop_inliner::<u8>(),
+        }];
+    }
+
     #[test]
     fn smoke_enum() {
         let a = Dolsot::Bim(Beta {
@@ -1843,4 +3405,714 @@ mod test {
             out,
         );
     }
+
+    #[test]
+    fn safe_round_trip() {
+        let a = Alpha {
+            a: 1,
+            b: 256,
+            c: 65536,
+            d: -1,
+            e: -129,
+            f: -32769,
+        };
+
+        let mut buf = [0u8; 64];
+        let used = to_slice(&a, &mut buf).unwrap();
+        assert_eq!(used, &[1, 128, 2, 128, 128, 4, 255, 129, 2, 129, 128, 4]);
+
+        let out: Alpha = from_bytes(used).unwrap();
+        assert_eq!(a, out);
+
+        let mut bytes = used.to_vec();
+        bytes.extend_from_slice(&[10, 20]);
+        let (out, rest) = take_from_bytes::<Alpha>(&bytes).unwrap();
+        assert_eq!(a, out);
+        assert_eq!(rest, &[10, 20]);
+
+        // Not enough room, or not enough data
+        assert!(to_slice(&a, &mut buf[..11]).is_err());
+        assert!(from_bytes::<Alpha>(&bytes[..11]).is_err());
+    }
+
+    #[test]
+    fn vec_round_trip() {
+        let a: Vec<u32> = (0..1000).map(|i| i * 1000).collect();
+        let used = to_vec(&a).unwrap();
+        let mut buf = [0u8; 4096];
+        assert_eq!(used, to_slice(&a, &mut buf).unwrap());
+        assert_eq!(a, from_bytes::<Vec<u32>>(&used).unwrap());
+
+        let mut ext = vec![0xAA, 0xBB];
+        to_extend(&(1u8, 2u16), &mut ext).unwrap();
+        assert_eq!(ext, &[0xAA, 0xBB, 1, 2]);
+
+        // A failed serialization leaves the vec alone
+        let mut ext = vec![0xAA, 0xBB];
+        assert_eq!(to_extend(&BadSer, &mut ext), Err(Error::BufferFull));
+        assert_eq!(ext, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn flavor_round_trip() {
+        use core::hash::Hasher;
+        use std::collections::hash_map::DefaultHasher;
+
+        let a: Vec<u32> = (0..100).map(|i| i * 1000).collect();
+        let expected = to_vec(&a).unwrap();
+
+        // A scratch buffer much smaller than the output
+        let mut scratch = [0u8; 7];
+        let out = to_io(&a, Vec::new(), &mut scratch).unwrap();
+        assert_eq!(out, expected);
+
+        let mut scratch = [0u8; 3];
+        let mut size = flavors::Size::new(&mut scratch);
+        to_flavor(&a, &mut size).unwrap();
+        assert_eq!(size.size(), expected.len());
+
+        // Hash on the way into a vec
+        let mut out = Vec::new();
+        let mut scratch = [0u8; 5];
+        let hash = flavors::Hash(DefaultHasher::new());
+        let mut stacked = flavors::Stacked::new(hash, &mut out, &mut scratch);
+        to_flavor(&a, &mut stacked).unwrap();
+        let hash = stacked.into_transform().0.finish();
+        assert_eq!(out, expected);
+        let mut check = DefaultHasher::new();
+        check.write(&expected);
+        assert_eq!(hash, check.finish());
+
+        #[cfg(feature = "heapless")]
+        {
+            let mut out = heapless::Vec::<u8, 512>::new();
+            to_flavor(&a, &mut out).unwrap();
+            assert_eq!(out.as_slice(), expected);
+
+            let mut out = heapless::Vec::<u8, 16>::new();
+            assert_eq!(to_flavor(&a, &mut out), Err(Error::BufferFull));
+        }
+    }
+
+    #[test]
+    fn borrowed_round_trip() {
+        type Borrowed<'a> = (&'a str, &'a [u8], &'a [u8; 4], Option<&'a str>);
+        let val: Borrowed = ("hello", &[1, 2, 3], &[4, 5, 6, 7], Some("world"));
+        let mut buf = [0u8; 64];
+        let used = to_slice(&val, &mut buf).unwrap();
+        let owned = (
+            String::from("hello"),
+            vec![1u8, 2, 3],
+            [4u8, 5, 6, 7],
+            Some("world"),
+        );
+        assert_eq!(used, to_vec(&owned).unwrap());
+
+        let out: Borrowed = from_bytes(used).unwrap();
+        assert_eq!(out, val);
+        // Points into the input, no copies
+        assert!(used.as_ptr_range().contains(&out.0.as_ptr()));
+
+        assert_eq!(from_bytes::<&str>(&[2, b'a', 0xFF]), Err(Error::BadUtf8));
+        assert_eq!(
+            from_bytes::<&[u8; 4]>(&[1, 2, 3]),
+            Err(Error::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn pointer_round_trip() {
+        use std::{rc::Rc, sync::Arc};
+
+        type Ptrs = (Box<u32>, Box<[u16]>, Box<str>, Rc<Dolsot>, Arc<str>);
+        let val: Ptrs = (
+            Box::new(1234),
+            Box::new([1, 300, 3]),
+            "hello".into(),
+            Rc::new(Dolsot::Bap(5)),
+            "world".into(),
+        );
+        let used = to_vec(&val).unwrap();
+        let plain = (
+            1234u32,
+            vec![1u16, 300, 3],
+            "hello",
+            Dolsot::Bap(5),
+            "world",
+        );
+        assert_eq!(used, to_vec(&plain).unwrap());
+        assert_eq!(from_bytes::<Ptrs>(&used).unwrap(), val);
+        assert_eq!(Box::<u64>::MAX_SIZE, u64::MAX_SIZE);
+
+        // Nothing is leaked or left half-built on a short input
+        assert_eq!(
+            from_bytes::<(Box<String>, Arc<[String]>)>(&used[..8]),
+            Err(Error::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn char_and_unit_round_trip() {
+        type Misc = (char, (), PhantomData<String>, char);
+        let val: Misc = ('a', (), PhantomData, '🦀');
+        let used = to_vec(&val).unwrap();
+        assert_eq!(used, to_vec(&("a", "🦀")).unwrap());
+        assert_eq!(from_bytes::<Misc>(&used).unwrap(), val);
+        assert_eq!(to_vec(&()).unwrap(), &[]);
+        assert_eq!(Misc::MAX_SIZE, 10);
+        assert_eq!(Misc::MIN_SIZE, 4);
+    }
+
+    #[test]
+    fn nonzero_round_trip() {
+        use core::num::{NonZeroI32, NonZeroU16, NonZeroU8};
+
+        type Nz = (NonZeroU8, NonZeroU16, NonZeroI32, Option<NonZeroU16>);
+        let val: Nz = (
+            NonZeroU8::new(1).unwrap(),
+            NonZeroU16::new(300).unwrap(),
+            NonZeroI32::new(-1).unwrap(),
+            NonZeroU16::new(u16::MAX),
+        );
+        let used = to_vec(&val).unwrap();
+        assert_eq!(used, to_vec(&(1u8, 300u16, -1i32, Some(u16::MAX))).unwrap());
+        assert_eq!(from_bytes::<Nz>(&used).unwrap(), val);
+        assert_eq!(Nz::MAX_SIZE, <(u8, u16, i32, Option<u16>)>::MAX_SIZE);
+
+        assert_eq!(from_bytes::<NonZeroU16>(&[0]), Err(Error::BadNonZero));
+        assert_eq!(from_bytes::<NonZeroI32>(&[0]), Err(Error::BadNonZero));
+        assert_eq!(
+            from_bytes::<Option<NonZeroU16>>(&[1, 0]),
+            Err(Error::BadNonZero)
+        );
+        // Still uses the niche
+        assert_eq!(core::mem::size_of::<Option<NonZeroU16>>(), 2);
+        assert_eq!(from_bytes::<Option<NonZeroU16>>(&[0]).unwrap(), None);
+    }
+
+    #[test]
+    fn time_round_trip() {
+        use std::time::{Duration, SystemTime};
+
+        let val = Duration::new(300, 999_999_999);
+        let used = to_vec(&val).unwrap();
+        assert_eq!(used, to_vec(&(300u64, 999_999_999u32)).unwrap());
+        assert_eq!(from_bytes::<Duration>(&used).unwrap(), val);
+        assert_eq!(
+            from_bytes::<Duration>(&to_vec(&(1u64, 1_000_000_000u32)).unwrap()),
+            Err(Error::BadTime)
+        );
+        assert_eq!(Duration::MAX_SIZE, 10 + 5);
+
+        let now = SystemTime::now();
+        let since = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let used = to_vec(&now).unwrap();
+        assert_eq!(used, to_vec(&since).unwrap());
+        assert_eq!(from_bytes::<SystemTime>(&used).unwrap(), now);
+
+        let before = SystemTime::UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(to_vec(&before), Err(Error::BadTime));
+    }
+
+    #[test]
+    fn result_round_trip() {
+        type Res = core::result::Result<String, u16>;
+        let ok: Res = Ok(String::from("hi"));
+        let used = to_vec(&ok).unwrap();
+        assert_eq!(used, &[0, 2, b'h', b'i']);
+        assert_eq!(from_bytes::<Res>(&used).unwrap(), ok);
+
+        let err: Res = Err(300);
+        let used = to_vec(&err).unwrap();
+        assert_eq!(used, &[1, 172, 2]);
+        assert_eq!(from_bytes::<Res>(&used).unwrap(), err);
+
+        assert_eq!(from_bytes::<Res>(&[2, 0]), Err(Error::BadDiscriminant));
+        assert_eq!(from_bytes::<Res>(&[0, 2, b'h']), Err(Error::UnexpectedEnd));
+        type Bounded = core::result::Result<u8, (u32, bool)>;
+        assert_eq!(Bounded::MAX_SIZE, 1 + 5 + 1);
+        assert_eq!(Bounded::MIN_SIZE, 1 + 1);
+    }
+
+    #[test]
+    fn map_round_trip() {
+        use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+        let btree: BTreeMap<u8, String> = [(1, "one".into()), (2, "two".into())].into();
+        let used = to_vec(&btree).unwrap();
+        assert_eq!(used, &[2, 1, 3, b'o', b'n', b'e', 2, 3, b't', b'w', b'o']);
+        assert_eq!(from_bytes::<BTreeMap<u8, String>>(&used).unwrap(), btree);
+
+        let hash: HashMap<u8, String> = btree.clone().into_iter().collect();
+        let used = to_vec(&hash).unwrap();
+        assert_eq!(from_bytes::<HashMap<u8, String>>(&used).unwrap(), hash);
+
+        let set: BTreeSet<u16> = [300, 1, 2].into();
+        let used = to_vec(&set).unwrap();
+        assert_eq!(used, &[3, 1, 2, 172, 2]);
+        assert_eq!(from_bytes::<BTreeSet<u16>>(&used).unwrap(), set);
+        let set: HashSet<u16> = set.into_iter().collect();
+        assert_eq!(from_bytes::<HashSet<u16>>(&used).unwrap(), set);
+
+        // A key without its value
+        assert_eq!(
+            from_bytes::<BTreeMap<u8, String>>(&[2, 1, 1, b'a', 2]),
+            Err(Error::UnexpectedEnd)
+        );
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_round_trip() {
+        type Hl = (heapless::String<8>, heapless::Vec<u16, 4>);
+        let mut val: Hl = Default::default();
+        val.0.push_str("hello").unwrap();
+        val.1.extend_from_slice(&[1, 300, 3]).unwrap();
+
+        let used = to_vec(&val).unwrap();
+        assert_eq!(used, to_vec(&("hello", vec![1u16, 300, 3])).unwrap());
+        assert_eq!(from_bytes::<Hl>(&used).unwrap(), val);
+        assert!(used.len() <= Hl::MAX_SIZE);
+
+        // Longer than the capacity is an error, not a panic
+        let long = to_vec(&vec![1u16; 5]).unwrap();
+        assert_eq!(
+            from_bytes::<heapless::Vec<u16, 4>>(&long),
+            Err(Error::TooLong)
+        );
+        let long = to_vec(&"123456789").unwrap();
+        assert_eq!(
+            from_bytes::<heapless::String<8>>(&long),
+            Err(Error::TooLong)
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        let val = (
+            u64::MAX,
+            -1i8,
+            String::from("hello"),
+            vec![[1u16, 300]; 100],
+            Some(Dolsot::Bap(1234)),
+        );
+        assert_eq!(serialized_size(&val).unwrap(), to_vec(&val).unwrap().len());
+        assert_eq!(serialized_size(&Vec::<u8>::new()).unwrap(), 1);
+
+        // Longer than any scratch window, and checked against a plain slice
+        let long = (
+            String::from("a fairly long string to measure"),
+            [u64::MAX; 4],
+        );
+        let mut buf = [0u8; 128];
+        let used = to_slice(&long, &mut buf).unwrap().len();
+        assert!(used > 32);
+        assert_eq!(serialized_size(&long).unwrap(), used);
+        assert_eq!(serialized_size(&BadSer), Err(Error::BufferFull));
+    }
+
+    #[test]
+    fn max_sizes() {
+        type Bounded = (bool, u16, i64, [u128; 2], Option<(f32, u8)>);
+        assert_eq!(Bounded::MAX_SIZE, 1 + 3 + 10 + 2 * 19 + 1 + 5);
+        assert_eq!(Bounded::MIN_SIZE, 1 + 1 + 1 + 2 + 1);
+
+        let val: Bounded = (true, u16::MAX, i64::MIN, [u128::MAX; 2], Some((0.0, 1)));
+        assert_eq!(serialized_size(&val).unwrap(), Bounded::MAX_SIZE);
+        let val: Bounded = (false, 0, 0, [0; 2], None);
+        assert_eq!(serialized_size(&val).unwrap(), Bounded::MIN_SIZE);
+    }
+
+    #[test]
+    fn reader_round_trip() {
+        use sources::IoReader;
+
+        /// Hands out one byte per read
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let a = (String::from("hello, world"), vec![300u16; 20], -5i64);
+        let b = (String::from("bye"), vec![1u16], 0i64);
+        let mut bytes = to_vec(&a).unwrap();
+        to_extend(&b, &mut bytes).unwrap();
+
+        let mut buf = [0u8; 16];
+        let mut reader = IoReader::new(Trickle(&bytes), &mut buf);
+        assert_eq!(
+            reader.deserialize::<(String, Vec<u16>, i64)>(),
+            Ok(a.clone())
+        );
+        assert_eq!(reader.deserialize::<(String, Vec<u16>, i64)>(), Ok(b));
+        assert_eq!(reader.deserialize::<u8>(), Err(Error::UnexpectedEnd));
+
+        // Ending partway through is an error, not a short value
+        let mut buf = [0u8; 16];
+        let mut reader = IoReader::new(&bytes[..10], &mut buf);
+        assert_eq!(
+            reader.deserialize::<(String, Vec<u16>, i64)>(),
+            Err(Error::UnexpectedEnd)
+        );
+
+        // The string doesn't fit in the buffer
+        let mut buf = [0u8; 8];
+        let mut reader = IoReader::new(&bytes[..], &mut buf);
+        assert_eq!(
+            reader.deserialize::<(String, Vec<u16>, i64)>(),
+            Err(Error::BufferFull)
+        );
+
+        // Bytes from a source can't outlive the next pop, so can't be borrowed
+        let mut buf = [0u8; 8];
+        let mut reader = IoReader::new(&[1u8, 2, 3][..], &mut buf);
+        let mut stream = DeserStream::from_source(&mut reader);
+        assert_eq!(stream.pop_n(2), Err(Error::NotBorrowable));
+        assert_eq!(stream.pop_n_buffered(2), Ok(&[1u8, 2][..]));
+
+        #[cfg(feature = "embedded-io")]
+        {
+            let mut buf = [0u8; 16];
+            let mut reader = sources::EioReader::new(&bytes[..], &mut buf);
+            assert_eq!(reader.deserialize::<(String, Vec<u16>, i64)>(), Ok(a));
+        }
+    }
+
+    #[cfg(feature = "embedded-io-async")]
+    #[test]
+    fn async_round_trip() {
+        use core::{
+            future::{poll_fn, Future},
+            pin::pin,
+            task::{Context, Poll, Waker},
+        };
+
+        /// Hands out one byte per read, and is only ready every other poll
+        struct Trickle<'a> {
+            bytes: &'a [u8],
+            ready: bool,
+        }
+
+        impl embedded_io_async::ErrorType for Trickle<'_> {
+            type Error = core::convert::Infallible;
+        }
+
+        impl embedded_io_async::Read for Trickle<'_> {
+            async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, Self::Error> {
+                poll_fn(|cx| {
+                    self.ready = !self.ready;
+                    if !self.ready {
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                    let n = self.bytes.len().min(buf.len()).min(1);
+                    buf[..n].copy_from_slice(&self.bytes[..n]);
+                    self.bytes = &self.bytes[n..];
+                    Poll::Ready(Ok(n))
+                })
+                .await
+            }
+        }
+
+        fn block_on<F: Future>(fut: F) -> F::Output {
+            let mut fut = pin!(fut);
+            let mut cx = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                    return out;
+                }
+            }
+        }
+
+        let a = (String::from("hello"), vec![300u16; 5], -5i64);
+        let mut out = [0u8; 64];
+        let mut writer = &mut out[..];
+        let mut buf = [0u8; 64];
+        block_on(asynch::serialize(&a, &mut writer, &mut buf)).unwrap();
+        block_on(asynch::serialize(&7u8, &mut writer, &mut buf)).unwrap();
+        let used = 64 - writer.len();
+        let bytes = &out[..used];
+        // The same encoding as the blocking functions
+        let (back, rest) = take_from_bytes::<(String, Vec<u16>, i64)>(bytes).unwrap();
+        assert_eq!(back, a);
+        assert_eq!(rest, [7]);
+
+        let mut reader = asynch::AsyncReader::new(
+            Trickle {
+                bytes,
+                ready: false,
+            },
+            &mut buf,
+        );
+
+        // Drop a few futures partway through, nothing read so far is lost
+        for _ in 0..5 {
+            let mut fut = pin!(reader.deserialize::<(String, Vec<u16>, i64)>());
+            let mut cx = Context::from_waker(Waker::noop());
+            for _ in 0..3 {
+                assert!(fut.as_mut().poll(&mut cx).is_pending());
+            }
+        }
+        assert_eq!(block_on(reader.deserialize()), Ok(a.clone()));
+        assert_eq!(block_on(reader.deserialize()), Ok(7u8));
+        assert_eq!(
+            block_on(reader.deserialize::<u8>()),
+            Err(Error::UnexpectedEnd)
+        );
+
+        // Messages from `to_slice` read back, and a bad one doesn't take the
+        // next one with it
+        let mut out = [0u8; 64];
+        let first = to_slice(&a, &mut out).unwrap().len();
+        let mut bytes = out[..first].to_vec();
+        bytes.extend_from_slice(&[9, 1]);
+        let mut buf = [0u8; 64];
+        let mut reader = asynch::AsyncReader::new(
+            Trickle {
+                bytes: &bytes,
+                ready: false,
+            },
+            &mut buf,
+        );
+        assert_eq!(block_on(reader.deserialize()), Ok(a.clone()));
+        assert_eq!(block_on(reader.deserialize::<bool>()), Err(Error::BadBool));
+        assert_eq!(block_on(reader.deserialize()), Ok(true));
+
+        // Framed messages carry their length
+        let mut out = [0u8; 64];
+        let mut writer = &mut out[..];
+        let mut buf = [0u8; 64];
+        block_on(asynch::serialize_framed(&a, &mut writer, &mut buf)).unwrap();
+        let used = 64 - writer.len();
+        let body = to_vec(&a).unwrap();
+        assert_eq!(out[0] as usize, body.len());
+        assert_eq!(out[1..used], body);
+        let mut reader = asynch::AsyncReader::new(
+            Trickle {
+                bytes: &out[..used],
+                ready: false,
+            },
+            &mut buf,
+        );
+        assert_eq!(block_on(reader.deserialize_framed()), Ok(a));
+
+        // A frame that doesn't decode, and one too long for the buffer, are
+        // dropped without losing the frames after them
+        let bytes = [1, 9, 1, 1, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+        let mut buf = [0u8; 8];
+        let mut reader = asynch::AsyncReader::new(
+            Trickle {
+                bytes: &bytes,
+                ready: false,
+            },
+            &mut buf,
+        );
+        assert_eq!(
+            block_on(reader.deserialize_framed::<bool>()),
+            Err(Error::BadBool)
+        );
+        assert_eq!(block_on(reader.deserialize_framed()), Ok(true));
+        assert_eq!(
+            block_on(reader.deserialize_framed::<bool>()),
+            Err(Error::BufferFull)
+        );
+        assert_eq!(block_on(reader.deserialize_framed()), Ok(false));
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        let mut buf = [0u8; 1024];
+        let used = to_slice_cobs(&[0x11u8, 0x22, 0x00, 0x33], &mut buf).unwrap();
+        assert_eq!(used, &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+
+        // Runs around the 254 byte block limit, with and without zeros
+        for len in [0, 1, 253, 254, 255, 508, 600] {
+            for zero_every in [1, 7, 254, 255, usize::MAX] {
+                let data: Vec<u8> = (0..len)
+                    .map(|i| {
+                        if i % zero_every == 0 {
+                            0
+                        } else {
+                            1 + (i % 250) as u8
+                        }
+                    })
+                    .collect();
+                let mut enc = to_vec_cobs(&data).unwrap();
+                assert_eq!(enc.iter().position(|b| *b == 0), Some(enc.len() - 1));
+                assert_eq!(from_bytes_cobs::<Vec<u8>>(&mut enc).unwrap(), data);
+            }
+        }
+
+        let mut two = to_vec_cobs(&1234u32).unwrap();
+        to_extend(&0u8, &mut two).unwrap();
+        let (val, rest) = take_from_bytes_cobs::<u32>(&mut two).unwrap();
+        assert_eq!((val, &*rest), (1234, &[0u8][..]));
+
+        assert_eq!(
+            from_bytes_cobs::<u8>(&mut [0x05, 0x01, 0x00]),
+            Err(Error::BadCobs)
+        );
+        let mut small = [0u8; 4];
+        assert_eq!(to_slice_cobs(&u32::MAX, &mut small), Err(Error::BufferFull));
+    }
+
+    #[test]
+    fn cobs_accumulator() {
+        use cobs::{CobsAccumulator, FeedResult};
+
+        let mut stream = to_vec_cobs(&(1u8, 300u16)).unwrap();
+        stream.extend_from_slice(&[0x05, 0x01, 0x00]);
+        stream.extend(to_vec_cobs(&(2u8, 0u16)).unwrap());
+
+        let mut acc = CobsAccumulator::<16>::new();
+        let mut got = Vec::new();
+        // One byte at a time, as it comes off the wire
+        for byte in stream.chunks(1) {
+            let mut window = byte;
+            while !window.is_empty() {
+                window = match acc.feed::<(u8, u16)>(window) {
+                    FeedResult::Consumed => break,
+                    FeedResult::OverFull(rest) => rest,
+                    FeedResult::DeserError(rest) => {
+                        got.push(None);
+                        rest
+                    }
+                    FeedResult::Success { data, remaining } => {
+                        got.push(Some(data));
+                        remaining
+                    }
+                };
+            }
+        }
+        assert_eq!(got, [Some((1, 300)), None, Some((2, 0))]);
+
+        let mut acc = CobsAccumulator::<4>::new();
+        let frame = to_vec_cobs(&u32::MAX).unwrap();
+        let res = acc.feed::<u32>(&frame);
+        assert!(matches!(res, FeedResult::OverFull(&[])));
+
+        // An oversized frame split across chunks is dropped as a whole, and
+        // the frame after it still decodes
+        let big = to_vec_cobs(&[0xAAu8; 8]).unwrap();
+        let (head, tail) = big.split_at(6);
+        assert!(matches!(acc.feed::<u32>(head), FeedResult::Consumed));
+        let mut rest = tail.to_vec();
+        rest.extend(to_vec_cobs(&7u32).unwrap());
+        let FeedResult::OverFull(next) = acc.feed::<u32>(&rest) else {
+            panic!("oversized frame was not dropped");
+        };
+        assert!(matches!(
+            acc.feed::<u32>(next),
+            FeedResult::Success {
+                data: 7,
+                remaining: &[]
+            }
+        ));
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    fn crc_round_trip() {
+        use crc::{Crc, CRC_16_IBM_SDLC, CRC_32_ISCSI};
+        const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+        const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+        let val = (5u8, String::from("hello"), -1234i32);
+        let mut buf = [0u8; 64];
+        let used = to_slice_crc16(&val, &mut buf, CRC16.digest()).unwrap();
+        let (payload, trailer) = used.split_at(used.len() - 2);
+        assert_eq!(trailer, CRC16.checksum(payload).to_le_bytes());
+        assert_eq!(
+            from_bytes_crc16::<(u8, String, i32)>(used, CRC16.digest()).unwrap(),
+            val
+        );
+
+        let mut used = to_vec_crc32(&val, CRC32.digest()).unwrap();
+        assert_eq!(used.len(), payload.len() + 4);
+        assert_eq!(
+            from_bytes_crc32::<(u8, String, i32)>(&used, CRC32.digest()).unwrap(),
+            val
+        );
+
+        // A flipped bit is caught before anything is decoded
+        used[1] ^= 0x01;
+        assert_eq!(
+            from_bytes_crc32::<(u8, String, i32)>(&used, CRC32.digest()),
+            Err(Error::ChecksumMismatch)
+        );
+        assert_eq!(
+            from_bytes_crc32::<u8>(&[1, 2, 3], CRC32.digest()),
+            Err(Error::UnexpectedEnd)
+        );
+        assert_eq!(Error::ChecksumMismatch.to_string(), "checksum mismatch");
+
+        // The same thing as a flavor, under COBS
+        let mut out = Vec::new();
+        let mut framed = flavors::Cobs::new(&mut out);
+        let mut scratch = [0u8; 4];
+        let crc = flavors::Crc16(CRC16.digest());
+        to_flavor(
+            &val,
+            &mut flavors::Stacked::new(crc, &mut framed, &mut scratch),
+        )
+        .unwrap();
+        let len = cobs::decode_in_place(&mut out).unwrap();
+        assert_eq!(&out[..len], &buf[..len]);
+    }
+
+    #[test]
+    fn error_kinds() {
+        let mut buf = [0u8; 2];
+        assert_eq!(to_slice(&u32::MAX, &mut buf), Err(Error::BufferFull));
+        assert_eq!(from_bytes::<u32>(&[0x80]), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            from_bytes::<u16>(&[0xFF, 0xFF, 0x04]),
+            Err(Error::BadVarint)
+        );
+        assert_eq!(
+            from_bytes::<u16>(&[0xFF, 0xFF, 0xFF]),
+            Err(Error::BadVarint)
+        );
+        assert_eq!(from_bytes::<bool>(&[2]), Err(Error::BadBool));
+        assert_eq!(from_bytes::<String>(&[1, 0xFF]), Err(Error::BadUtf8));
+        assert_eq!(from_bytes::<Option<u8>>(&[2, 0]), Err(Error::BadOptionTag));
+        assert_eq!(from_bytes::<Dolsot>(&[4]), Err(Error::BadDiscriminant));
+        assert_eq!(from_bytes::<char>(&[2, b'a', b'b']), Err(Error::BadChar));
+        assert_eq!(from_bytes::<char>(&[0]), Err(Error::BadChar));
+        assert_eq!(from_bytes::<char>(&[5, 0, 0, 0, 0, 0]), Err(Error::BadChar));
+        assert_eq!(from_bytes::<char>(&[1, 0xFF]), Err(Error::BadUtf8));
+    }
+
+    #[test]
+    fn drops_partial_values() {
+        // A tuple whose last field is a bad bool
+        let bytes = &[1, 2, 3, 4, 9];
+        let res = from_bytes::<(Tracked, Vec<Tracked>, bool)>(bytes);
+        assert_eq!(res, Err(Error::BadBool));
+        assert_eq!(TRACKED_DROPS.swap(0, Ordering::Relaxed), 3);
+
+        // A vec and an array that are cut short
+        let res = from_bytes::<Vec<Tracked>>(&[4, 1, 2, 3]);
+        assert_eq!(res, Err(Error::UnexpectedEnd));
+        assert_eq!(TRACKED_DROPS.swap(0, Ordering::Relaxed), 3);
+
+        let res = from_bytes::<[Tracked; 4]>(&[1, 2]);
+        assert_eq!(res, Err(Error::UnexpectedEnd));
+        assert_eq!(TRACKED_DROPS.swap(0, Ordering::Relaxed), 2);
+
+        let res = from_bytes::<Option<(Tracked, Tracked)>>(&[1, 1]);
+        assert_eq!(res, Err(Error::UnexpectedEnd));
+        assert_eq!(TRACKED_DROPS.swap(0, Ordering::Relaxed), 1);
+
+        // A map whose last entry is cut short
+        let res = from_bytes::<std::collections::BTreeMap<u8, Tracked>>(&[3, 1, 1, 2, 2, 3]);
+        assert_eq!(res, Err(Error::UnexpectedEnd));
+        assert_eq!(TRACKED_DROPS.swap(0, Ordering::Relaxed), 2);
+    }
 }
@@ -0,0 +1,191 @@
+//! Encoded size bounds for types without unbounded collections
+
+use crate::ser_varint::varint_max;
+
+/// The largest and smallest number of bytes a value of this type can
+/// serialize to
+///
+/// This is not implemented for `Vec`, `String` or anything containing them,
+/// as there is no upper bound on their size:
+///
+/// ```compile_fail
+/// use postcard_forth::MaxSize;
+///
+/// let _ = <(u8, Vec<u8>) as MaxSize>::MAX_SIZE;
+/// ```
+pub trait MaxSize {
+    const MAX_SIZE: usize;
+    const MIN_SIZE: usize;
+}
+
+/// `const` version of [`Ord::max`], for use by the derive
+pub const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// `const` version of [`Ord::min`], for use by the derive
+pub const fn min(a: usize, b: usize) -> usize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// The number of bytes `val` takes up as a varint
+pub const fn varint_size(mut val: u64) -> usize {
+    let mut size = 1;
+    while val >= 0x80 {
+        val >>= 7;
+        size += 1;
+    }
+    size
+}
+
+macro_rules! fixed {
+    ($($ty:ty => $size:expr),* $(,)?) => {
+        $(
+            impl MaxSize for $ty {
+                const MAX_SIZE: usize = $size;
+                const MIN_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+fixed! {
+    bool => 1,
+    u8 => 1,
+    i8 => 1,
+    f32 => 4,
+    f64 => 8,
+    () => 0,
+}
+
+macro_rules! varint {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl MaxSize for $ty {
+                const MAX_SIZE: usize = varint_max::<$ty>();
+                const MIN_SIZE: usize = 1;
+            }
+        )*
+    };
+}
+
+varint!(u16, u32, u64, u128, usize, i16, i32, i64, i128, isize);
+
+/// A length byte, then one to four bytes of UTF-8
+impl MaxSize for char {
+    const MAX_SIZE: usize = 5;
+    const MIN_SIZE: usize = 2;
+}
+
+/// The seconds as a `u64`, then the nanoseconds as a `u32`
+impl MaxSize for core::time::Duration {
+    const MAX_SIZE: usize = u64::MAX_SIZE + u32::MAX_SIZE;
+    const MIN_SIZE: usize = 2;
+}
+
+#[cfg(feature = "std")]
+impl MaxSize for std::time::SystemTime {
+    const MAX_SIZE: usize = core::time::Duration::MAX_SIZE;
+    const MIN_SIZE: usize = core::time::Duration::MIN_SIZE;
+}
+
+impl<T: ?Sized> MaxSize for core::marker::PhantomData<T> {
+    const MAX_SIZE: usize = 0;
+    const MIN_SIZE: usize = 0;
+}
+
+impl<T: MaxSize, const N: usize> MaxSize for [T; N] {
+    const MAX_SIZE: usize = T::MAX_SIZE * N;
+    const MIN_SIZE: usize = T::MIN_SIZE * N;
+}
+
+impl<T: MaxSize> MaxSize for Option<T> {
+    const MAX_SIZE: usize = 1 + T::MAX_SIZE;
+    const MIN_SIZE: usize = 1;
+}
+
+impl<T: MaxSize, E: MaxSize> MaxSize for Result<T, E> {
+    const MAX_SIZE: usize = 1 + max(T::MAX_SIZE, E::MAX_SIZE);
+    const MIN_SIZE: usize = 1 + min(T::MIN_SIZE, E::MIN_SIZE);
+}
+
+#[cfg(feature = "alloc")]
+impl<T: MaxSize> MaxSize for alloc::boxed::Box<T> {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+    const MIN_SIZE: usize = T::MIN_SIZE;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: MaxSize> MaxSize for alloc::rc::Rc<T> {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+    const MIN_SIZE: usize = T::MIN_SIZE;
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T: MaxSize> MaxSize for alloc::sync::Arc<T> {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+    const MIN_SIZE: usize = T::MIN_SIZE;
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> MaxSize for heapless::String<N> {
+    const MAX_SIZE: usize = varint_size(N as u64) + N;
+    const MIN_SIZE: usize = 1;
+}
+
+#[cfg(feature = "heapless")]
+impl<T: MaxSize, const N: usize> MaxSize for heapless::Vec<T, N> {
+    const MAX_SIZE: usize = varint_size(N as u64) + T::MAX_SIZE * N;
+    const MIN_SIZE: usize = 1;
+}
+
+macro_rules! nonzero {
+    ($($nz:ident($int:ty)),* $(,)?) => {
+        $(
+            impl MaxSize for core::num::$nz {
+                const MAX_SIZE: usize = <$int>::MAX_SIZE;
+                const MIN_SIZE: usize = <$int>::MIN_SIZE;
+            }
+        )*
+    };
+}
+
+nonzero!(
+    NonZeroU8(u8),
+    NonZeroU16(u16),
+    NonZeroU32(u32),
+    NonZeroU64(u64),
+    NonZeroU128(u128),
+    NonZeroUsize(usize),
+    NonZeroI8(i8),
+    NonZeroI16(i16),
+    NonZeroI32(i32),
+    NonZeroI64(i64),
+    NonZeroI128(i128),
+    NonZeroIsize(isize),
+);
+
+macro_rules! tuple {
+    ($($ty:ident),*) => {
+        impl<$($ty: MaxSize),*> MaxSize for ($($ty,)*) {
+            const MAX_SIZE: usize = 0 $(+ $ty::MAX_SIZE)*;
+            const MIN_SIZE: usize = 0 $(+ $ty::MIN_SIZE)*;
+        }
+    };
+}
+
+tuple!(T);
+tuple!(T, U);
+tuple!(T, U, V);
+tuple!(T, U, V, W);
+tuple!(T, U, V, W, X);
+tuple!(T, U, V, W, X, Y);
+tuple!(T, U, V, W, X, Y, Z);
@@ -0,0 +1,183 @@
+//! Input sources for [`DeserStream`](crate::DeserStream)
+//!
+//! By default a `DeserStream` reads from a single slice holding the whole
+//! message. A [`Source`] instead hands the stream a window of input, and is
+//! asked for more whenever that window runs out, so messages can be read
+//! straight from a reader.
+
+use crate::Result;
+
+#[cfg(any(feature = "std", feature = "embedded-io"))]
+use crate::{from_source, DeserializeOwned, Error};
+
+/// Something that a [`DeserStream`](crate::DeserStream) reads from, one
+/// window at a time
+pub trait Source {
+    /// Drop the first `used` bytes of the last window, and return a new one.
+    ///
+    /// The new window starts with the rest of the last window, and should
+    /// hold at least `min` bytes. A shorter window means the input ended.
+    /// The first call has a `used` of zero.
+    fn refill(&mut self, used: usize, min: usize) -> Result<&[u8]>;
+
+    /// Drop the first `used` bytes of the last window, no more are needed
+    /// for now.
+    ///
+    /// The rest of the window is kept for the next message.
+    fn finish(&mut self, used: usize) -> Result<()>;
+}
+
+/// The buffer shared by the readers, holding the unused bytes in
+/// `buf[start..end]`
+#[cfg(any(feature = "std", feature = "embedded-io"))]
+struct Buffer<'b> {
+    buf: &'b mut [u8],
+    start: usize,
+    end: usize,
+}
+
+#[cfg(any(feature = "std", feature = "embedded-io"))]
+impl<'b> Buffer<'b> {
+    fn new(buf: &'b mut [u8]) -> Self {
+        Buffer {
+            buf,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Read with `read` until we have `min` bytes or it reports the end of
+    /// the input by returning zero.
+    fn refill(
+        &mut self,
+        used: usize,
+        min: usize,
+        mut read: impl FnMut(&mut [u8]) -> Result<usize>,
+    ) -> Result<&[u8]> {
+        self.start += used;
+        if min > self.buf.len() {
+            return Err(Error::BufferFull);
+        }
+        if self.end - self.start < min {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+            while self.end < min {
+                match read(&mut self.buf[self.end..])? {
+                    0 => break,
+                    n => self.end += n,
+                }
+            }
+        }
+        Ok(&self.buf[self.start..self.end])
+    }
+
+    fn finish(&mut self, used: usize) {
+        self.start += used;
+    }
+}
+
+/// Reads from a [`std::io::Read`], buffering in a scratch buffer
+///
+/// The buffer must be able to hold the longest string or byte array in the
+/// messages. Bytes read past the end of one message are kept for the next.
+#[cfg(feature = "std")]
+pub struct IoReader<'b, R: std::io::Read> {
+    buf: Buffer<'b>,
+    reader: R,
+}
+
+#[cfg(feature = "std")]
+impl<'b, R: std::io::Read> IoReader<'b, R> {
+    pub fn new(reader: R, buf: &'b mut [u8]) -> Self {
+        IoReader {
+            buf: Buffer::new(buf),
+            reader,
+        }
+    }
+
+    /// Deserialize the next `T`
+    ///
+    /// A reader that ends partway through the `T` gives an
+    /// [`Error::UnexpectedEnd`]. Types that borrow from the input can't be
+    /// read this way, as the buffer is reused:
+    ///
+    /// ```compile_fail
+    /// # use postcard_forth::sources::IoReader;
+    /// let mut buf = [0u8; 16];
+    /// let mut reader = IoReader::new(&[1, b'a'][..], &mut buf);
+    /// let s: &str = reader.deserialize().unwrap();
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&mut self) -> Result<T> {
+        from_source(self)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Source for IoReader<'_, R> {
+    fn refill(&mut self, used: usize, min: usize) -> Result<&[u8]> {
+        let reader = &mut self.reader;
+        self.buf.refill(used, min, |buf| loop {
+            match reader.read(buf) {
+                Ok(n) => return Ok(n),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err(Error::Io),
+            }
+        })
+    }
+
+    fn finish(&mut self, used: usize) -> Result<()> {
+        self.buf.finish(used);
+        Ok(())
+    }
+}
+
+/// Reads from an [`embedded_io::Read`], buffering in a scratch buffer
+///
+/// The buffer must be able to hold the longest string or byte array in the
+/// messages. Bytes read past the end of one message are kept for the next.
+#[cfg(feature = "embedded-io")]
+pub struct EioReader<'b, R: embedded_io::Read> {
+    buf: Buffer<'b>,
+    reader: R,
+}
+
+#[cfg(feature = "embedded-io")]
+impl<'b, R: embedded_io::Read> EioReader<'b, R> {
+    pub fn new(reader: R, buf: &'b mut [u8]) -> Self {
+        EioReader {
+            buf: Buffer::new(buf),
+            reader,
+        }
+    }
+
+    /// Deserialize the next `T`
+    ///
+    /// A reader that ends partway through the `T` gives an
+    /// [`Error::UnexpectedEnd`].
+    pub fn deserialize<T: DeserializeOwned>(&mut self) -> Result<T> {
+        from_source(self)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R: embedded_io::Read> Source for EioReader<'_, R> {
+    fn refill(&mut self, used: usize, min: usize) -> Result<&[u8]> {
+        let reader = &mut self.reader;
+        self.buf
+            .refill(used, min, |buf| reader.read(buf).map_err(|_| Error::Io))
+    }
+
+    fn finish(&mut self, used: usize) -> Result<()> {
+        self.buf.finish(used);
+        Ok(())
+    }
+}
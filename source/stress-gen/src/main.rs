@@ -87,15 +87,10 @@ fn deserialize<T: ::serde::de::DeserializeOwned>(
 
 #[cfg(feature = "postcard-forth")]
 #[inline]
-fn deserialize<T: ::postcard_forth::Deserialize>(
+fn deserialize<T: ::postcard_forth::DeserializeOwned>(
     in_buf: &[u8],
 ) -> Result<T, ()> {{
-    let mut desers = ::postcard_forth::DeserStream::from(in_buf);
-    let mut out = core::mem::MaybeUninit::<T>::uninit();
-    unsafe {{
-        ::postcard_forth::deser_fields_ref(&mut desers, &mut out).map_err(drop)?;
-    }}
-    Ok(unsafe {{ out.assume_init() }})
+    ::postcard_forth::from_bytes::<T>(in_buf).map_err(drop)
 }}
 
 #[cfg(feature = "serde")]
@@ -113,14 +108,7 @@ fn serialize<T: ::postcard_forth::Serialize>(
     t: &T,
     out_buf: &mut [u8],
 ) -> Result<usize, ()> {{
-    let olen = out_buf.len();
-    let mut sers = ::postcard_forth::SerStream::from(out_buf);
-    unsafe {{
-        ::postcard_forth::ser_fields_ref(&mut sers, t).map_err(drop)?;
-    }}
-    let remain = sers.remain();
-    let used = olen - remain;
-    Ok(used)
+    ::postcard_forth::to_slice(t, out_buf).map_err(drop).map(|u| u.len())
 }}
 
     "#);